@@ -0,0 +1,111 @@
+//! Conversions between SOL (or token) amounts and lamports (or token base units).
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+/// A decimal amount of SOL or tokens as typed. It is kept as digits rather than a float so that it
+/// converts to base units exactly: spec addresses are derived from the reward in base units, and
+/// `0.29 * 1e9` rounds down to 289999999.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UiAmount {
+    whole: u64,
+    /// Digits after the decimal point, without trailing zeros.
+    fraction: String,
+}
+
+impl UiAmount {
+    /// The amount in base units of a currency with `decimals` decimal places. Fails if the amount
+    /// has more decimal places than the currency or does not fit in a `u64`.
+    pub fn to_base_units(&self, decimals: u8) -> anyhow::Result<u64> {
+        let decimals = decimals as usize;
+        if self.fraction.len() > decimals {
+            return Err(anyhow!(
+                "{} has more than {} decimal places",
+                self,
+                decimals
+            ));
+        }
+        format!("{}{:0<width$}", self.whole, self.fraction, width = decimals)
+            .parse()
+            .map_err(|_| anyhow!("{} is too large", self))
+    }
+}
+
+impl FromStr for UiAmount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |digits: &str| digits.bytes().all(|byte| byte.is_ascii_digit());
+        if whole.is_empty() && fraction.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(anyhow!("Invalid amount {:?}", s));
+        }
+        Ok(Self {
+            whole: if whole.is_empty() { 0 } else { whole.parse()? },
+            fraction: fraction.trim_end_matches('0').to_string(),
+        })
+    }
+}
+
+impl fmt::Display for UiAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.whole)?;
+        if !self.fraction.is_empty() {
+            write!(f, ".{}", self.fraction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Approximate amount in SOL (or tokens), for JSON records.
+pub fn to_ui_amount(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+/// Exact amount in SOL (or tokens), for display. Trailing zeros are dropped.
+pub fn format_ui_amount(amount: u64, decimals: u8) -> String {
+    let digits = format!("{:0>width$}", amount, width = decimals as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_units(amount: &str, decimals: u8) -> anyhow::Result<u64> {
+        amount.parse::<UiAmount>()?.to_base_units(decimals)
+    }
+
+    #[test]
+    fn parses_exactly() {
+        assert_eq!(base_units("0.29", 9).unwrap(), 290_000_000);
+        assert_eq!(base_units("1", 9).unwrap(), 1_000_000_000);
+        assert_eq!(base_units(".5", 9).unwrap(), 500_000_000);
+        assert_eq!(base_units("2.", 6).unwrap(), 2_000_000);
+        assert_eq!(base_units("0.000000001", 9).unwrap(), 1);
+        assert_eq!(base_units("1.50", 1).unwrap(), 15);
+        assert_eq!(base_units("18446744073.709551615", 9).unwrap(), u64::MAX);
+        assert!(base_units("0.0000000001", 9).is_err());
+        assert!(base_units("18446744073.709551616", 9).is_err());
+        for invalid in ["", ".", "-1", "1e9", "0.1.2", " 1"] {
+            assert!(invalid.parse::<UiAmount>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn formats_exactly() {
+        assert_eq!(format_ui_amount(290_000_000, 9), "0.29");
+        assert_eq!(format_ui_amount(289_999_999, 9), "0.289999999");
+        assert_eq!(format_ui_amount(1_000_000_000, 9), "1");
+        assert_eq!(format_ui_amount(u64::MAX, 9), "18446744073.709551615");
+        assert_eq!(format_ui_amount(42, 0), "42");
+    }
+}
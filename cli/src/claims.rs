@@ -0,0 +1,493 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use itertools::Itertools;
+use solana_account_decoder::UiAccountEncoding;
+use solana_address_lookup_table_program::instruction::{create_lookup_table, extend_lookup_table};
+use solana_address_lookup_table_program::state::{
+    AddressLookupTable, LOOKUP_TABLE_MAX_ADDRESSES, LOOKUP_TABLE_META_SIZE,
+};
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{v0, VersionedMessage};
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::{Transaction, TransactionError, VersionedTransaction};
+
+/// How long a fetched blockhash is reused. Blockhashes stay valid for 150 blocks (about a minute),
+/// so this leaves claims plenty of time to land.
+const BLOCKHASH_TTL: Duration = Duration::from_secs(10);
+
+/// Addresses added to a lookup table per transaction. Each one takes 32 bytes of the packet.
+const LOOKUP_TABLE_ADDRESSES_PER_TRANSACTION: usize = 20;
+
+/// Why a claim transaction did not land.
+#[derive(Debug)]
+pub enum ClaimError {
+    /// The transaction could not be compiled or signed.
+    Build(String),
+    /// The RPC node rejected the transaction, usually because preflight simulation failed.
+    Send(ClientError),
+    /// The transaction landed but failed.
+    Failed(TransactionError),
+    /// The blockhash expired before the transaction was confirmed.
+    Expired,
+}
+
+impl ClaimError {
+    pub fn transaction_error(&self) -> Option<TransactionError> {
+        match self {
+            ClaimError::Send(error) => error.get_transaction_error(),
+            ClaimError::Failed(error) => Some(error.clone()),
+            ClaimError::Build(_) | ClaimError::Expired => None,
+        }
+    }
+}
+
+impl fmt::Display for ClaimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClaimError::Build(error) => write!(f, "{}", error),
+            ClaimError::Send(error) => write!(f, "{}", error),
+            ClaimError::Failed(error) => write!(f, "{}", error),
+            ClaimError::Expired => write!(f, "transaction expired before it was confirmed"),
+        }
+    }
+}
+
+/// Claims sent together in one transaction, each with its instruction and caller context.
+struct Batch<T> {
+    /// Signs alongside the payer, unless the payer is the only signer the claims need.
+    signer: Option<Arc<dyn Signer>>,
+    claims: Vec<(Instruction, T)>,
+}
+
+impl<T> Batch<T> {
+    fn instructions(&self) -> Vec<Instruction> {
+        self.claims
+            .iter()
+            .map(|(instruction, _)| instruction.clone())
+            .collect()
+    }
+}
+
+struct InFlight<T> {
+    signature: Signature,
+    last_valid_block_height: u64,
+    batch: Batch<T>,
+}
+
+/// Sends claim transactions without waiting for each one to confirm. Up to `capacity` transactions
+/// are tracked at once, and `poll` checks all of their signatures in a single RPC call. Each claim
+/// carries a caller-defined `context` that comes back with its outcome.
+///
+/// Claims submitted together are packed into as few transactions as fit. When a packed transaction
+/// fails, its claims are split up and resent so one bad claim can't sink the others.
+pub struct ClaimPool<'a, T> {
+    client: &'a RpcClient,
+    payer: &'a Keypair,
+    commitment: CommitmentConfig,
+    capacity: usize,
+    lookup_tables: Vec<AddressLookupTableAccount>,
+    compute_unit_price: u64,
+    blockhash: Option<(Hash, u64, Instant)>,
+    in_flight: Vec<InFlight<T>>,
+    /// Batches that failed before they could be tracked, settled on the next `poll`.
+    failed: Vec<(Batch<T>, ClaimError)>,
+}
+
+impl<'a, T> ClaimPool<'a, T> {
+    pub fn new(
+        client: &'a RpcClient,
+        payer: &'a Keypair,
+        commitment: CommitmentConfig,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            client,
+            payer,
+            commitment,
+            capacity,
+            lookup_tables: vec![],
+            compute_unit_price: 0,
+            blockhash: None,
+            in_flight: vec![],
+            failed: vec![],
+        }
+    }
+
+    /// Compiles claim transactions against `lookup_table` to fit more claims in each.
+    pub fn use_lookup_table(&mut self, lookup_table: AddressLookupTableAccount) {
+        self.lookup_tables.push(lookup_table);
+    }
+
+    /// Pays a priority fee of `compute_unit_price` micro-lamports per compute unit on every claim
+    /// transaction.
+    pub fn set_compute_unit_price(&mut self, compute_unit_price: u64) {
+        self.compute_unit_price = compute_unit_price;
+    }
+
+    /// Whether another batch of claims can be submitted.
+    pub fn has_capacity(&self) -> bool {
+        self.len() < self.capacity
+    }
+
+    /// Number of transactions whose outcome has not been settled yet.
+    pub fn len(&self) -> usize {
+        self.in_flight.len() + self.failed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `instructions` fit in a single transaction signed by the payer and at most one other
+    /// signer.
+    pub fn fits(&self, instructions: &[Instruction]) -> bool {
+        let message = match v0::Message::try_compile(
+            &self.payer.pubkey(),
+            &self.with_compute_budget(instructions.to_vec()),
+            &self.lookup_tables,
+            Hash::default(),
+        ) {
+            Ok(message) => message,
+            Err(_) => return false,
+        };
+        // Signatures are a fixed size, so placeholders give the exact serialized size
+        let num_signatures = message.header.num_required_signatures as usize;
+        let transaction = VersionedTransaction {
+            signatures: vec![Signature::default(); num_signatures],
+            message: VersionedMessage::V0(message),
+        };
+        bincode::serialized_size(&transaction).is_ok_and(|size| size as usize <= PACKET_DATA_SIZE)
+    }
+
+    /// Appends the priority fee instruction, if there is one. It goes last so that the index of a
+    /// failed instruction is also the index of its claim.
+    fn with_compute_budget(&self, mut instructions: Vec<Instruction>) -> Vec<Instruction> {
+        if self.compute_unit_price > 0 {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(
+                self.compute_unit_price,
+            ));
+        }
+        instructions
+    }
+
+    async fn latest_blockhash(&mut self) -> Result<(Hash, u64), ClientError> {
+        if let Some((blockhash, last_valid_block_height, fetched)) = self.blockhash {
+            if fetched.elapsed() < BLOCKHASH_TTL {
+                return Ok((blockhash, last_valid_block_height));
+            }
+        }
+        let (blockhash, last_valid_block_height) = self
+            .client
+            .get_latest_blockhash_with_commitment(self.commitment)
+            .await?;
+        self.blockhash = Some((blockhash, last_valid_block_height, Instant::now()));
+        Ok((blockhash, last_valid_block_height))
+    }
+
+    /// Packs `claims` into as few transactions as fit, signs them with the payer and `signer`, if
+    /// any, and sends them without waiting for confirmation.
+    pub async fn submit(&mut self, signer: Option<Arc<dyn Signer>>, claims: Vec<(Instruction, T)>) {
+        let mut batch = Batch {
+            signer: signer.clone(),
+            claims: vec![],
+        };
+        for (instruction, context) in claims {
+            let mut instructions = batch.instructions();
+            instructions.push(instruction.clone());
+            if !batch.claims.is_empty() && !self.fits(&instructions) {
+                let full = std::mem::replace(
+                    &mut batch,
+                    Batch {
+                        signer: signer.clone(),
+                        claims: vec![],
+                    },
+                );
+                self.send(full).await;
+            }
+            batch.claims.push((instruction, context));
+        }
+        if !batch.claims.is_empty() {
+            self.send(batch).await;
+        }
+    }
+
+    async fn send(&mut self, batch: Batch<T>) {
+        let (blockhash, last_valid_block_height) = match self.latest_blockhash().await {
+            Ok(blockhash) => blockhash,
+            Err(error) => {
+                self.failed.push((batch, ClaimError::Send(error)));
+                return;
+            }
+        };
+        let transaction = v0::Message::try_compile(
+            &self.payer.pubkey(),
+            &self.with_compute_budget(batch.instructions()),
+            &self.lookup_tables,
+            blockhash,
+        )
+        .map_err(|error| error.to_string())
+        .and_then(|message| {
+            let signers = [self.payer as &dyn Signer]
+                .into_iter()
+                .chain(batch.signer.as_deref())
+                .collect::<Vec<_>>();
+            VersionedTransaction::try_new(VersionedMessage::V0(message), &signers)
+                .map_err(|error| error.to_string())
+        });
+        let transaction = match transaction {
+            Ok(transaction) => transaction,
+            Err(error) => {
+                self.failed.push((batch, ClaimError::Build(error)));
+                return;
+            }
+        };
+        match self.client.send_transaction(&transaction).await {
+            Ok(signature) => self.in_flight.push(InFlight {
+                signature,
+                last_valid_block_height,
+                batch,
+            }),
+            Err(error) => self.failed.push((batch, ClaimError::Send(error))),
+        }
+    }
+
+    /// Reports a failed single claim, or splits up a failed batch and resends the parts. A failed
+    /// transaction applies none of its instructions, so the failing instruction is reported on its
+    /// own and the rest are resent together. Without an instruction to blame, the batch is halved.
+    async fn settle_failure(
+        &mut self,
+        mut batch: Batch<T>,
+        error: ClaimError,
+        done: &mut Vec<(T, Result<Signature, ClaimError>)>,
+    ) {
+        if batch.claims.len() == 1 {
+            let (_, context) = batch.claims.remove(0);
+            done.push((context, Err(error)));
+            return;
+        }
+        match error.transaction_error() {
+            Some(TransactionError::InstructionError(index, instruction_error))
+                if (index as usize) < batch.claims.len() =>
+            {
+                let (_, context) = batch.claims.remove(index as usize);
+                let error =
+                    ClaimError::Failed(TransactionError::InstructionError(0, instruction_error));
+                done.push((context, Err(error)));
+                self.send(batch).await;
+            }
+            _ => {
+                let second = Batch {
+                    signer: batch.signer.clone(),
+                    claims: batch.claims.split_off(batch.claims.len() / 2),
+                };
+                self.send(batch).await;
+                self.send(second).await;
+            }
+        }
+    }
+
+    /// Returns the claims that have confirmed, failed or expired since the last poll. Claims from
+    /// failed batches that were resent are reported once their new transaction settles.
+    pub async fn poll(&mut self) -> Result<Vec<(T, Result<Signature, ClaimError>)>, ClientError> {
+        let mut done = vec![];
+        let mut failed = std::mem::take(&mut self.failed);
+
+        if !self.in_flight.is_empty() {
+            let signatures = self
+                .in_flight
+                .iter()
+                .map(|in_flight| in_flight.signature)
+                .collect::<Vec<_>>();
+            let statuses = match self.client.get_signature_statuses(&signatures).await {
+                Ok(statuses) => statuses.value,
+                Err(error) => {
+                    self.failed = failed;
+                    return Err(error);
+                }
+            };
+            // Only look up the block height if some transaction is still unaccounted for
+            let block_height = if statuses.iter().any(Option::is_none) {
+                match self.client.get_block_height().await {
+                    Ok(block_height) => block_height,
+                    Err(error) => {
+                        self.failed = failed;
+                        return Err(error);
+                    }
+                }
+            } else {
+                0
+            };
+
+            let mut pending = vec![];
+            for (in_flight, status) in self.in_flight.drain(..).zip(statuses) {
+                match status {
+                    Some(status) if status.satisfies_commitment(self.commitment) => {
+                        match status.err {
+                            Some(error) => {
+                                failed.push((in_flight.batch, ClaimError::Failed(error)))
+                            }
+                            None => done.extend(
+                                in_flight
+                                    .batch
+                                    .claims
+                                    .into_iter()
+                                    .map(|(_, context)| (context, Ok(in_flight.signature))),
+                            ),
+                        }
+                    }
+                    None if block_height > in_flight.last_valid_block_height => {
+                        failed.push((in_flight.batch, ClaimError::Expired));
+                    }
+                    _ => pending.push(in_flight),
+                }
+            }
+            self.in_flight = pending;
+        }
+
+        for (batch, error) in failed {
+            self.settle_failure(batch, error, &mut done).await;
+        }
+        Ok(done)
+    }
+}
+
+/// Offset of the authority in a serialized lookup table: the account type tag, deactivation slot,
+/// last extended slot and its start index, then the `Option` tag.
+const LOOKUP_TABLE_AUTHORITY_OFFSET: usize = 4 + 8 + 8 + 1 + 1;
+
+/// Lookup tables owned by `payer` that hold all of `addresses`, ready to be used.
+///
+/// Every table this creates starts with `marker`, and later calls reuse the payer's active tables
+/// that start with it, so repeated runs do not leave a new table behind each time. Addresses that
+/// are missing go into tables with room left first, then into new tables of up to
+/// [`LOOKUP_TABLE_MAX_ADDRESSES`] addresses each.
+pub async fn lookup_tables_for(
+    client: &RpcClient,
+    payer: &Keypair,
+    marker: Pubkey,
+    addresses: Vec<Pubkey>,
+) -> anyhow::Result<Vec<AddressLookupTableAccount>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                LOOKUP_TABLE_AUTHORITY_OFFSET,
+                payer.pubkey().as_ref(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                LOOKUP_TABLE_META_SIZE,
+                marker.as_ref(),
+            )),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let mut tables = client
+        .get_program_accounts_with_config(&solana_address_lookup_table_program::id(), config)
+        .await?
+        .into_iter()
+        .filter_map(|(key, account)| {
+            let table = AddressLookupTable::deserialize(&account.data).ok()?;
+            (table.meta.deactivation_slot == Slot::MAX).then(|| AddressLookupTableAccount {
+                key,
+                addresses: table.addresses.to_vec(),
+            })
+        })
+        .collect_vec();
+
+    let mut missing = addresses
+        .into_iter()
+        .unique()
+        .filter(|address| !tables.iter().any(|table| table.addresses.contains(address)))
+        .collect_vec();
+    if missing.is_empty() {
+        return Ok(tables);
+    }
+
+    for table in &mut tables {
+        let room = LOOKUP_TABLE_MAX_ADDRESSES.saturating_sub(table.addresses.len());
+        let added = missing.drain(..room.min(missing.len())).collect_vec();
+        extend_lookup_table_with(client, payer, table.key, None, &added).await?;
+        table.addresses.extend(added);
+    }
+    let mut last_recent_slot = None;
+    while !missing.is_empty() {
+        // The table address is derived from a recent slot, which must still be in SlotHashes, so
+        // each new table needs a slot of its own
+        let recent_slot = loop {
+            let slot = client
+                .get_slot_with_commitment(CommitmentConfig::finalized())
+                .await?;
+            if Some(slot) != last_recent_slot {
+                break slot;
+            }
+            tokio::time::sleep(Duration::from_millis(400)).await;
+        };
+        last_recent_slot = Some(recent_slot);
+        let (create_ix, key) = create_lookup_table(payer.pubkey(), payer.pubkey(), recent_slot);
+        let added = [marker]
+            .into_iter()
+            .chain(missing.drain(..(LOOKUP_TABLE_MAX_ADDRESSES - 1).min(missing.len())))
+            .collect_vec();
+        extend_lookup_table_with(client, payer, key, Some(create_ix), &added).await?;
+        tables.push(AddressLookupTableAccount {
+            key,
+            addresses: added,
+        });
+    }
+
+    // Addresses can only be looked up from the slot after they were added
+    let extended_slot = client.get_slot().await?;
+    while client.get_slot().await? <= extended_slot {
+        tokio::time::sleep(Duration::from_millis(400)).await;
+    }
+    Ok(tables)
+}
+
+/// Appends `addresses` to `lookup_table`, running `create_ix` first if the table is new.
+async fn extend_lookup_table_with(
+    client: &RpcClient,
+    payer: &Keypair,
+    lookup_table: Pubkey,
+    mut create_ix: Option<Instruction>,
+    addresses: &[Pubkey],
+) -> anyhow::Result<()> {
+    for chunk in addresses.chunks(LOOKUP_TABLE_ADDRESSES_PER_TRANSACTION) {
+        let extend_ix = extend_lookup_table(
+            lookup_table,
+            payer.pubkey(),
+            Some(payer.pubkey()),
+            chunk.to_vec(),
+        );
+        let ixs = create_ix
+            .take()
+            .into_iter()
+            .chain([extend_ix])
+            .collect::<Vec<_>>();
+        let transaction = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&payer.pubkey()),
+            &[payer],
+            client.get_latest_blockhash().await?,
+        );
+        client.send_and_confirm_transaction(&transaction).await?;
+    }
+    Ok(())
+}
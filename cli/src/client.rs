@@ -0,0 +1,669 @@
+use std::str::FromStr;
+
+use anchor_lang::AccountDeserialize;
+use anchor_lang::Discriminator;
+use anchor_lang::InstructionData;
+use anchor_lang::ToAccountMetas;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use anyhow::anyhow;
+use proof_of_work_faucet::{Difficulty, FaucetConfig, Mode, Receipt};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig,
+};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_client::rpc_request::MAX_MULTIPLE_ACCOUNTS;
+use solana_sdk::account::Account;
+use solana_sdk::clock::Clock;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::sysvar;
+use solana_sdk::transaction::Transaction;
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::grinder::Grinder;
+use crate::{
+    airdrop_ix, hash_airdrop_ix, parse_events, pda, priority_fee, ClaimCost, FaucetMetadata,
+    HistoryEntry, MintInfo, ReceiptAccount, CLAIM_COMPUTE_UNITS,
+};
+
+/// Typed access to the faucet program deployed at `program_id`, over RPC. Reads and transactions
+/// use the commitment of the wrapped `RpcClient`.
+pub struct FaucetClient {
+    rpc: RpcClient,
+    commitment: CommitmentConfig,
+    program_id: Pubkey,
+    /// Priority fee of transactions this client sends, in micro-lamports per compute unit.
+    compute_unit_price: u64,
+}
+
+impl FaucetClient {
+    /// `program_id` is usually the [`Cluster`](crate::cluster::Cluster)'s program ID, unless the
+    /// program was deployed somewhere else.
+    pub fn new(rpc: RpcClient, program_id: Pubkey) -> Self {
+        let commitment = rpc.commitment();
+        Self {
+            rpc,
+            commitment,
+            program_id,
+            compute_unit_price: 0,
+        }
+    }
+
+    /// Pays a priority fee of `compute_unit_price` micro-lamports per compute unit on every
+    /// transaction this client sends. Claim costs include the fee.
+    pub fn set_compute_unit_price(&mut self, compute_unit_price: u64) {
+        self.compute_unit_price = compute_unit_price;
+    }
+
+    pub fn compute_unit_price(&self) -> u64 {
+        self.compute_unit_price
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// The underlying RPC client, for requests this client has no method for.
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    /// Every faucet the program has created, found by the discriminator of their spec accounts.
+    pub async fn list(&self) -> anyhow::Result<Vec<FaucetMetadata>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                0,
+                &Difficulty::DISCRIMINATOR,
+            ))]),
+            account_config: spec_account_config(self.commitment),
+            ..RpcProgramAccountsConfig::default()
+        };
+        let clock = self.clock().await?;
+        let specs = self
+            .rpc
+            .get_program_accounts_with_config(&self.program_id, config)
+            .await?
+            .iter()
+            .filter_map(|(pubkey, account)| {
+                spec_metadata(*pubkey, account, &clock, &self.program_id)
+            })
+            .collect();
+        Ok(specs)
+    }
+
+    /// The faucet behind a spec account. `None` if the spec has not been created.
+    pub async fn get(&self, spec: &Pubkey) -> anyhow::Result<Option<FaucetMetadata>> {
+        let account = match self
+            .rpc
+            .get_account_with_commitment(spec, self.commitment)
+            .await?
+            .value
+        {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+        let difficulty = Difficulty::try_deserialize(&mut account.data.as_slice())?;
+        Ok(Some(FaucetMetadata::new(
+            *spec,
+            &difficulty,
+            &self.clock().await?,
+            &self.program_id,
+        )))
+    }
+
+    pub async fn clock(&self) -> anyhow::Result<Clock> {
+        let account = self.rpc.get_account(&sysvar::clock::id()).await?;
+        Ok(bincode::deserialize::<Clock>(&account.data)?)
+    }
+
+    /// Looks up the token program and decimals of a mint.
+    pub async fn mint_info(&self, mint: &Pubkey) -> anyhow::Result<MintInfo> {
+        Ok(self.mint_infos(&[*mint]).await?.remove(0))
+    }
+
+    /// Looks up several mints in as few requests as possible.
+    pub async fn mint_infos(&self, mints: &[Pubkey]) -> anyhow::Result<Vec<MintInfo>> {
+        self.get_multiple_accounts(mints)
+            .await?
+            .into_iter()
+            .zip(mints)
+            .map(|(account, mint)| {
+                let account = account.ok_or_else(|| anyhow!("Mint {} not found", mint))?;
+                let decimals = Mint::try_deserialize(&mut account.data.as_slice())?.decimals;
+                Ok(MintInfo {
+                    mint: *mint,
+                    token_program: account.owner,
+                    decimals,
+                })
+            })
+            .collect()
+    }
+
+    /// Balance of a faucet's source account, in lamports or token base units.
+    pub async fn balance(&self, metadata: &FaucetMetadata) -> anyhow::Result<u64> {
+        Ok(self.balances(std::slice::from_ref(metadata)).await?[0])
+    }
+
+    /// Balances of several faucets' source accounts, fetched in as few requests as possible. A
+    /// source that does not exist yet has a balance of zero.
+    pub async fn balances(&self, faucets: &[FaucetMetadata]) -> anyhow::Result<Vec<u64>> {
+        let sources = faucets
+            .iter()
+            .map(|metadata| metadata.faucet_pubkey)
+            .collect::<Vec<_>>();
+        self.get_multiple_accounts(&sources)
+            .await?
+            .into_iter()
+            .zip(faucets)
+            .map(|(account, metadata)| match (account, metadata.mint) {
+                (None, _) => Ok(0),
+                (Some(account), Some(_)) => {
+                    Ok(TokenAccount::try_deserialize(&mut account.data.as_slice())?.amount)
+                }
+                (Some(account), None) => Ok(account.lamports),
+            })
+            .collect()
+    }
+
+    /// Current rent and fees of a claim in `mode`, priced by the cluster from a sample claim. The
+    /// priority fee is priced at this client's compute unit price.
+    pub async fn claim_cost(&self, mode: Mode) -> anyhow::Result<ClaimCost> {
+        let receipt_rent = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(8 + Receipt::LEN)
+            .await?;
+        let empty_receipt_rent = self.rpc.get_minimum_balance_for_rent_exemption(0).await?;
+        let payer = Pubkey::new_unique();
+        let metadata = FaucetMetadata::default();
+        let ix = match mode {
+            Mode::Vanity => airdrop_ix(
+                &self.program_id,
+                &payer,
+                &Pubkey::new_unique(),
+                &metadata,
+                None,
+            ),
+            Mode::Hash => hash_airdrop_ix(&self.program_id, &payer, &metadata, 0, 0),
+        };
+        let message = Message::new_with_blockhash(
+            &[ix],
+            Some(&payer),
+            &self.rpc.get_latest_blockhash().await?,
+        );
+        let fee = self.rpc.get_fee_for_message(&message).await?;
+        Ok(ClaimCost {
+            receipt_rent,
+            empty_receipt_rent,
+            fee,
+            priority_fee: priority_fee(self.compute_unit_price, CLAIM_COMPUTE_UNITS),
+        })
+    }
+
+    /// Creates a faucet paying `amount` lamports per claim, or base units of `mint_info`'s mint.
+    /// `authority` pays for the spec and becomes the faucet's authority.
+    pub async fn create(
+        &self,
+        authority: &Keypair,
+        difficulty: u8,
+        amount: u64,
+        config: FaucetConfig,
+        mint_info: Option<&MintInfo>,
+    ) -> anyhow::Result<Signature> {
+        let spec = pda::spec(
+            difficulty,
+            amount,
+            config.mode,
+            mint_info.map(|mint_info| mint_info.mint),
+            &self.program_id,
+        );
+        let ix = match mint_info {
+            Some(mint_info) => Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::CreateToken {
+                    payer: authority.pubkey(),
+                    spec,
+                    mint: mint_info.mint,
+                    source: pda::source(&spec, &self.program_id),
+                    token_program: mint_info.token_program,
+                    system_program: solana_sdk::system_program::id(),
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::CreateToken {
+                    difficulty,
+                    amount,
+                    config,
+                }
+                .data(),
+            },
+            None => Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::Create {
+                    payer: authority.pubkey(),
+                    spec,
+                    system_program: solana_sdk::system_program::id(),
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::Create {
+                    difficulty,
+                    amount,
+                    config,
+                }
+                .data(),
+            },
+        };
+        Ok(self.send(&[ix], authority, &[]).await?)
+    }
+
+    /// Tops up a faucet with `amount` lamports, or base units of `mint_info`'s mint taken from the
+    /// funder's associated token account.
+    pub async fn fund(
+        &self,
+        funder: &Keypair,
+        spec: &Pubkey,
+        amount: u64,
+        mint_info: Option<&MintInfo>,
+    ) -> anyhow::Result<Signature> {
+        let ix = match mint_info {
+            Some(mint_info) => Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::FundToken {
+                    funder: funder.pubkey(),
+                    spec: *spec,
+                    mint: mint_info.mint,
+                    source: pda::source(spec, &self.program_id),
+                    funder_token_account: get_associated_token_address_with_program_id(
+                        &funder.pubkey(),
+                        &mint_info.mint,
+                        &mint_info.token_program,
+                    ),
+                    token_program: mint_info.token_program,
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::FundToken { amount }.data(),
+            },
+            None => Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::Fund {
+                    funder: funder.pubkey(),
+                    spec: *spec,
+                    source: pda::source(spec, &self.program_id),
+                    system_program: solana_sdk::system_program::id(),
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::Fund { amount }.data(),
+            },
+        };
+        Ok(self.send(&[ix], funder, &[]).await?)
+    }
+
+    /// Withdraws `amount` lamports, or base units of `mint_info`'s mint, from a faucet to its
+    /// authority. Token withdrawals go to the authority's associated token account, which is
+    /// created if needed.
+    pub async fn withdraw(
+        &self,
+        authority: &Keypair,
+        spec: &Pubkey,
+        amount: u64,
+        mint_info: Option<&MintInfo>,
+    ) -> anyhow::Result<Signature> {
+        let ix = match mint_info {
+            Some(mint_info) => Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::WithdrawToken {
+                    authority: authority.pubkey(),
+                    spec: *spec,
+                    mint: mint_info.mint,
+                    source: pda::source(spec, &self.program_id),
+                    destination: get_associated_token_address_with_program_id(
+                        &authority.pubkey(),
+                        &mint_info.mint,
+                        &mint_info.token_program,
+                    ),
+                    token_program: mint_info.token_program,
+                    associated_token_program: anchor_spl::associated_token::ID,
+                    system_program: solana_sdk::system_program::id(),
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::WithdrawToken { amount }.data(),
+            },
+            None => Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::Withdraw {
+                    authority: authority.pubkey(),
+                    spec: *spec,
+                    source: pda::source(spec, &self.program_id),
+                    system_program: solana_sdk::system_program::id(),
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::Withdraw { amount }.data(),
+            },
+        };
+        Ok(self.send(&[ix], authority, &[]).await?)
+    }
+
+    /// Closes a faucet, returning its remaining balance and the rent of its accounts to the
+    /// authority.
+    pub async fn close(
+        &self,
+        authority: &Keypair,
+        spec: &Pubkey,
+        mint_info: Option<&MintInfo>,
+    ) -> anyhow::Result<Signature> {
+        let ix = match mint_info {
+            Some(mint_info) => Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::CloseTokenFaucet {
+                    authority: authority.pubkey(),
+                    spec: *spec,
+                    mint: mint_info.mint,
+                    source: pda::source(spec, &self.program_id),
+                    destination: get_associated_token_address_with_program_id(
+                        &authority.pubkey(),
+                        &mint_info.mint,
+                        &mint_info.token_program,
+                    ),
+                    token_program: mint_info.token_program,
+                    associated_token_program: anchor_spl::associated_token::ID,
+                    system_program: solana_sdk::system_program::id(),
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::CloseTokenFaucet {}.data(),
+            },
+            None => Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::CloseFaucet {
+                    authority: authority.pubkey(),
+                    spec: *spec,
+                    source: pda::source(spec, &self.program_id),
+                    system_program: solana_sdk::system_program::id(),
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::CloseFaucet {}.data(),
+            },
+        };
+        Ok(self.send(&[ix], authority, &[]).await?)
+    }
+
+    /// Every receipt `payer` paid for that still holds data. Receipts without a cooldown, and ones
+    /// that were already closed, hold none.
+    pub async fn receipts(&self, payer: &Pubkey) -> anyhow::Result<Vec<ReceiptAccount>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(8 + Receipt::LEN as u64),
+                // Receipts are laid out as discriminator, spec, payer, ...
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(8 + 32, payer.as_ref())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(self.commitment),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let receipts = self
+            .rpc
+            .get_program_accounts_with_config(&self.program_id, config)
+            .await?
+            .into_iter()
+            .filter_map(|(pubkey, account)| {
+                let receipt = Receipt::try_deserialize(&mut account.data.as_slice()).ok()?;
+                Some(ReceiptAccount {
+                    pubkey,
+                    receipt,
+                    lamports: account.lamports,
+                })
+            })
+            .collect();
+        Ok(receipts)
+    }
+
+    /// Closes several of `payer`'s receipts in one transaction, refunding all but the rent of an
+    /// empty account. Every receipt's cooldown must have passed.
+    pub async fn close_receipts(
+        &self,
+        payer: &Keypair,
+        receipts: &[Pubkey],
+    ) -> Result<Signature, ClientError> {
+        let ixs = receipts
+            .iter()
+            .map(|receipt| Instruction {
+                program_id: self.program_id,
+                accounts: proof_of_work_faucet::accounts::CloseReceipt {
+                    payer: payer.pubkey(),
+                    receipt: *receipt,
+                }
+                .to_account_metas(None),
+                data: proof_of_work_faucet::instruction::CloseReceipt {}.data(),
+            })
+            .collect::<Vec<_>>();
+        self.send(&ixs, payer, &[]).await
+    }
+
+    /// The events of the faucet behind `spec` in its last `limit` successful transactions, oldest
+    /// first.
+    pub async fn history(&self, spec: &Pubkey, limit: usize) -> anyhow::Result<Vec<HistoryEntry>> {
+        let signatures = self
+            .rpc
+            .get_signatures_for_address_with_config(
+                spec,
+                GetConfirmedSignaturesForAddress2Config {
+                    limit: Some(limit),
+                    commitment: Some(self.commitment),
+                    ..GetConfirmedSignaturesForAddress2Config::default()
+                },
+            )
+            .await?;
+
+        let mut entries = vec![];
+        // Signatures come back newest first
+        for status in signatures.iter().rev() {
+            if status.err.is_some() {
+                continue;
+            }
+            let signature = Signature::from_str(&status.signature)?;
+            let transaction = self
+                .rpc
+                .get_transaction_with_config(
+                    &signature,
+                    RpcTransactionConfig {
+                        encoding: Some(UiTransactionEncoding::Json),
+                        commitment: Some(self.commitment),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )
+                .await?;
+            let logs: Option<Vec<String>> = match transaction.transaction.meta {
+                Some(meta) => meta.log_messages.into(),
+                None => None,
+            };
+            entries.extend(
+                parse_events(&logs.unwrap_or_default())
+                    .into_iter()
+                    // Events from other faucets can show up when a transaction touches several
+                    .filter(|event| event.spec() == *spec)
+                    .map(|event| HistoryEntry {
+                        slot: status.slot,
+                        signature,
+                        event,
+                    }),
+            );
+        }
+        Ok(entries)
+    }
+
+    /// Claims a vanity or token airdrop with `signer`, whose pubkey must meet the faucet's
+    /// difficulty. The error is left as a `ClientError` so callers can inspect it with
+    /// [`decode_faucet_error`](crate::decode_faucet_error).
+    pub async fn claim(
+        &self,
+        payer: &Keypair,
+        signer: &dyn Signer,
+        metadata: &FaucetMetadata,
+        mint_info: Option<&MintInfo>,
+    ) -> Result<Signature, ClientError> {
+        let ix = airdrop_ix(
+            &self.program_id,
+            &payer.pubkey(),
+            &signer.pubkey(),
+            metadata,
+            mint_info,
+        );
+        self.send(&[ix], payer, &[signer]).await
+    }
+
+    /// Grinds a keypair whose pubkey has a prefix of at least `difficulty` on `threads` threads.
+    /// This is offline work and makes no RPC requests.
+    pub async fn grind(&self, difficulty: u8, threads: usize) -> anyhow::Result<Keypair> {
+        let mut grinder = Grinder::spawn_keypairs(threads, difficulty);
+        match grinder.next().await {
+            Some(hit) => Ok(hit.signer),
+            None => Err(anyhow!("Grinder stopped before finding a keypair")),
+        }
+    }
+
+    /// `getMultipleAccounts` in chunks of the most accounts a single request accepts, so any number
+    /// of accounts can be fetched.
+    pub async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> anyhow::Result<Vec<Option<Account>>> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            accounts.extend(
+                self.rpc
+                    .get_multiple_accounts_with_commitment(chunk, self.commitment)
+                    .await?
+                    .value,
+            );
+        }
+        Ok(accounts)
+    }
+
+    async fn send(
+        &self,
+        ixs: &[Instruction],
+        payer: &Keypair,
+        signers: &[&dyn Signer],
+    ) -> Result<Signature, ClientError> {
+        let mut all_signers: Vec<&dyn Signer> = vec![payer];
+        all_signers.extend_from_slice(signers);
+        let mut ixs = ixs.to_vec();
+        if self.compute_unit_price > 0 {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+                self.compute_unit_price,
+            ));
+        }
+        let transaction = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&payer.pubkey()),
+            &all_signers,
+            self.rpc.get_latest_blockhash().await?,
+        );
+        self.rpc.send_and_confirm_transaction(&transaction).await
+    }
+}
+
+/// How spec accounts are requested from `getProgramAccounts`. A spec is larger than the 128 bytes
+/// RPC nodes are willing to encode as base58, so specs come back as base64.
+fn spec_account_config(commitment: CommitmentConfig) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(commitment),
+        ..RpcAccountInfoConfig::default()
+    }
+}
+
+/// Describes the faucet behind a fetched spec account. `None` if the account is not a current spec.
+fn spec_metadata(
+    spec: Pubkey,
+    account: &Account,
+    clock: &Clock,
+    program_id: &Pubkey,
+) -> Option<FaucetMetadata> {
+    let difficulty = Difficulty::try_deserialize(&mut account.data.as_slice()).ok()?;
+    Some(FaucetMetadata::new(spec, &difficulty, clock, program_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::AccountSerialize;
+    use proof_of_work_faucet::{
+        AdaptiveConfig, CapWindow, FaucetStats, PayoutPolicy, ThroughputCap,
+    };
+    use solana_account_decoder::{UiAccount, MAX_BASE58_BYTES};
+
+    use super::*;
+
+    #[test]
+    fn lists_full_size_specs() {
+        // Every optional field is set, so the spec takes up its full size
+        let difficulty = Difficulty {
+            difficulty: 3,
+            amount: 1_000_000,
+            authority: Pubkey::new_unique(),
+            config: FaucetConfig {
+                mode: Mode::Vanity,
+                adaptive: Some(AdaptiveConfig {
+                    target_claims_per_hour: 60,
+                    window: 3_600,
+                    min_difficulty: 2,
+                    max_difficulty: 5,
+                }),
+                cap: Some(ThroughputCap {
+                    window: CapWindow::Slots(1_000),
+                    max_lamports: Some(100_000_000),
+                    max_claims: Some(100),
+                }),
+                payout: PayoutPolicy::Partial,
+                receipt_cooldown: Some(216_000),
+            },
+            current_difficulty: 3,
+            window_start: 0,
+            window_claims: 0,
+            budget_window: 0,
+            budget_lamports_used: 0,
+            budget_claims_used: 0,
+            mint: Some(Pubkey::new_unique()),
+            stats: FaucetStats::default(),
+        };
+        let mut data = vec![];
+        difficulty.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + Difficulty::LEN);
+        assert!(data.len() > MAX_BASE58_BYTES);
+        let account = Account {
+            lamports: 1_000_000,
+            data,
+            owner: proof_of_work_faucet::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+
+        // Round trip the account through the encoding an RPC node would send it in
+        let spec = Pubkey::new_unique();
+        let fetch =
+            |encoding| UiAccount::encode(&spec, &account, encoding, None, None).decode::<Account>();
+        // Base58 can't carry a full spec, which would drop the faucet from the list
+        assert!(fetch(UiAccountEncoding::Binary).is_none());
+        let config = spec_account_config(CommitmentConfig::confirmed());
+        let fetched = fetch(config.encoding.unwrap()).unwrap();
+
+        let metadata =
+            spec_metadata(spec, &fetched, &Clock::default(), &proof_of_work_faucet::ID).unwrap();
+        assert_eq!(metadata.spec_pubkey, spec);
+        assert_eq!(metadata.amount, difficulty.amount);
+        assert_eq!(metadata.adaptive, difficulty.config.adaptive);
+        assert_eq!(metadata.mint, difficulty.mint);
+        assert_eq!(metadata.receipt_cooldown, Some(216_000));
+    }
+}
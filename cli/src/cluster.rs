@@ -0,0 +1,123 @@
+//! Clusters the faucet program is deployed to, and how to recognize them.
+
+use anyhow::anyhow;
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+
+/// Genesis hash of mainnet-beta. Faucets hand out free SOL, so mainnet is never allowed.
+pub const MAINNET_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cluster {
+    pub name: &'static str,
+    /// `None` for clusters whose genesis is regenerated, like a local test validator.
+    pub genesis_hash: Option<&'static str>,
+    /// Where the faucet program is deployed on this cluster, unless `--program-id` says otherwise.
+    pub program_id: Pubkey,
+}
+
+pub const DEVNET: Cluster = Cluster {
+    name: "devnet",
+    genesis_hash: Some("EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG"),
+    program_id: proof_of_work_faucet::ID,
+};
+
+pub const TESTNET: Cluster = Cluster {
+    name: "testnet",
+    genesis_hash: Some("4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY"),
+    program_id: proof_of_work_faucet::ID,
+};
+
+pub const LOCALNET: Cluster = Cluster {
+    name: "localnet",
+    genesis_hash: None,
+    program_id: proof_of_work_faucet::ID,
+};
+
+pub const CLUSTERS: &[Cluster] = &[DEVNET, TESTNET, LOCALNET];
+
+impl Cluster {
+    /// Works out which cluster an RPC endpoint serves from its genesis hash. A test validator gets
+    /// a fresh genesis every time it is reset, so localnet is recognized by a loopback URL instead.
+    /// Any other cluster must have its genesis hash in `allowed`, and runs the default program ID.
+    pub fn identify(genesis_hash: &Hash, url: &str, allowed: &[Hash]) -> anyhow::Result<Self> {
+        let genesis = genesis_hash.to_string();
+        if genesis == MAINNET_GENESIS_HASH {
+            return Err(anyhow!("Refusing to run against mainnet-beta"));
+        }
+        if let Some(cluster) = CLUSTERS
+            .iter()
+            .find(|cluster| cluster.genesis_hash == Some(genesis.as_str()))
+        {
+            return Ok(*cluster);
+        }
+        if is_loopback(url) {
+            return Ok(LOCALNET);
+        }
+        if allowed.contains(genesis_hash) {
+            return Ok(Cluster {
+                name: "custom",
+                genesis_hash: None,
+                program_id: proof_of_work_faucet::ID,
+            });
+        }
+        Err(anyhow!(
+            "Unknown cluster with genesis hash {}. Pass `--allow-cluster {}` to use it anyway",
+            genesis,
+            genesis
+        ))
+    }
+}
+
+fn is_loopback(url: &str) -> bool {
+    let authority = url.split("://").last().unwrap_or(url);
+    // IPv6 addresses are bracketed, since they are full of colons themselves
+    let host = match authority.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or(bracketed),
+        None => authority.split(['/', ':']).next().unwrap_or(authority),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "0.0.0.0" | "::1")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn identifies_clusters() {
+        let mainnet = Hash::from_str(MAINNET_GENESIS_HASH).unwrap();
+        let devnet = Hash::from_str(DEVNET.genesis_hash.unwrap()).unwrap();
+        let custom = Hash::new_unique();
+
+        // Mainnet is refused even when it is allowed or served from a loopback address
+        assert!(Cluster::identify(&mainnet, "https://api.mainnet-beta.solana.com", &[]).is_err());
+        assert!(Cluster::identify(&mainnet, "http://localhost:8899", &[mainnet]).is_err());
+
+        assert_eq!(
+            Cluster::identify(&devnet, "https://api.devnet.solana.com", &[]).unwrap(),
+            DEVNET
+        );
+
+        // A test validator's genesis changes on every reset, so any loopback URL is localnet
+        for url in [
+            "http://localhost:8899",
+            "http://127.0.0.1:8899",
+            "ws://0.0.0.0:8900/",
+            "http://[::1]:8899",
+            "http://[::1]",
+        ] {
+            assert_eq!(Cluster::identify(&custom, url, &[]).unwrap(), LOCALNET);
+        }
+        assert!(Cluster::identify(&custom, "http://localhost.example.com", &[]).is_err());
+        assert!(Cluster::identify(&custom, "http://[2001:db8::1]:8899", &[]).is_err());
+
+        let url = "https://my-cluster.example.com";
+        assert!(Cluster::identify(&custom, url, &[]).is_err());
+        assert!(Cluster::identify(&custom, url, &[Hash::new_unique()]).is_err());
+        let cluster = Cluster::identify(&custom, url, &[Hash::new_unique(), custom]).unwrap();
+        assert_eq!(cluster.name, "custom");
+        assert_eq!(cluster.program_id, proof_of_work_faucet::ID);
+    }
+}
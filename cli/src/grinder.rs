@@ -0,0 +1,438 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+
+use clap::ValueEnum;
+use curve25519_dalek::constants::{ED25519_BASEPOINT_POINT, ED25519_BASEPOINT_TABLE};
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::{ExpandedSecretKey, PublicKey};
+use proof_of_work_faucet::{challenge_hash, leading_zero_bits};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::signer::{Signer, SignerError};
+use tokio::sync::mpsc;
+
+use crate::prefix::PrefixScorer;
+
+/// Found keys waiting to be claimed. Grinder threads wait for room when this fills up, since a hit
+/// that is dropped is work thrown away.
+const HIT_BUFFER: usize = 64;
+
+/// Threads add to the shared key counter in batches to keep it from bouncing between cores.
+const COUNT_BATCH: u64 = 1024;
+
+/// How grinder threads generate candidate pubkeys.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GrinderMode {
+    /// Generate a fresh keypair for every candidate
+    #[default]
+    Keypair,
+    /// Step from one candidate pubkey to the next by adding the ed25519 base point, which is far
+    /// cheaper than a full scalar multiplication. Hits sign with a raw scalar instead of a seed, so
+    /// they cannot be exported as Solana keypair files
+    PointAddition,
+}
+
+/// A signer whose pubkey meets the minimum prefix length, along with its prefix length. `S` is
+/// [`GroundSigner`] for grinders that may use any mode and `Keypair` for keypair-only grinders.
+pub struct Hit<S = GroundSigner> {
+    pub signer: S,
+    pub prefix_len: usize,
+}
+
+/// The signer behind a hit, which depends on the grinder mode that found it.
+pub enum GroundSigner {
+    Keypair(Keypair),
+    Scalar(ScalarSigner),
+}
+
+impl Signer for GroundSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        match self {
+            GroundSigner::Keypair(keypair) => keypair.try_pubkey(),
+            GroundSigner::Scalar(signer) => signer.try_pubkey(),
+        }
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        match self {
+            GroundSigner::Keypair(keypair) => keypair.try_sign_message(message),
+            GroundSigner::Scalar(signer) => signer.try_sign_message(message),
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Signs with a bare secret scalar. Point addition finds the scalar of a hit but never a seed that
+/// hashes to it, so hits can't be turned back into a regular `Keypair`.
+pub struct ScalarSigner {
+    secret: ExpandedSecretKey,
+    public: PublicKey,
+}
+
+impl ScalarSigner {
+    fn new(scalar: Scalar, public: PublicKey) -> Self {
+        // The nonce only has to be secret and unique per key, so hits never share one
+        let nonce: [u8; 32] = rand::random();
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(scalar.as_bytes());
+        bytes[32..].copy_from_slice(&nonce);
+        Self {
+            secret: ExpandedSecretKey::from_bytes(&bytes).unwrap(),
+            public,
+        }
+    }
+}
+
+impl Signer for ScalarSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(Pubkey::new_from_array(self.public.to_bytes()))
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        Ok(Signature::new(
+            &self.secret.sign(message, &self.public).to_bytes(),
+        ))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Grinds vanity keypairs on dedicated OS threads and hands hits to the async claim path over a
+/// channel. The threads stop when the grinder is dropped.
+pub struct Grinder<S = GroundSigner> {
+    hits: mpsc::Receiver<Hit<S>>,
+    shared: Arc<Shared>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+/// State shared between the grinder and its threads.
+#[derive(Default)]
+struct Shared {
+    min_prefix_len: AtomicU8,
+    stop: AtomicBool,
+    keys_tried: AtomicU64,
+}
+
+impl Grinder {
+    pub fn spawn(mode: GrinderMode, threads: usize, min_prefix_len: u8) -> Self {
+        Self::spawn_with(threads, min_prefix_len, move |sender, shared| match mode {
+            GrinderMode::Keypair => grind_keypairs(sender, shared, GroundSigner::Keypair),
+            GrinderMode::PointAddition => grind_points(sender, shared),
+        })
+    }
+}
+
+impl Grinder<Keypair> {
+    /// Grinds in [`GrinderMode::Keypair`] and hands out plain keypairs, for callers that save or
+    /// export what they find.
+    pub fn spawn_keypairs(threads: usize, min_prefix_len: u8) -> Self {
+        Self::spawn_with(threads, min_prefix_len, |sender, shared| {
+            grind_keypairs(sender, shared, |keypair| keypair)
+        })
+    }
+}
+
+impl<S: Send + 'static> Grinder<S> {
+    fn spawn_with<F>(threads: usize, min_prefix_len: u8, grind: F) -> Self
+    where
+        F: Fn(mpsc::Sender<Hit<S>>, &Shared) + Clone + Send + 'static,
+    {
+        let (sender, hits) = mpsc::channel(HIT_BUFFER);
+        let shared = Arc::new(Shared {
+            min_prefix_len: AtomicU8::new(min_prefix_len),
+            ..Shared::default()
+        });
+        let handles = (0..threads.max(1))
+            .map(|_| {
+                let sender = sender.clone();
+                let shared = shared.clone();
+                let grind = grind.clone();
+                std::thread::spawn(move || grind(sender, &shared))
+            })
+            .collect();
+        Self {
+            hits,
+            shared,
+            handles,
+        }
+    }
+
+    /// Only keys with at least this many leading 'A's are reported from now on.
+    pub fn set_min_prefix_len(&self, min_prefix_len: u8) {
+        self.shared
+            .min_prefix_len
+            .store(min_prefix_len, Ordering::Relaxed);
+    }
+
+    /// Total number of candidate keys checked so far, across all threads.
+    pub fn keys_tried(&self) -> u64 {
+        self.shared.keys_tried.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the next key that meets the minimum prefix length.
+    pub async fn next(&mut self) -> Option<Hit<S>> {
+        self.hits.recv().await
+    }
+}
+
+impl<S> Drop for Grinder<S> {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        // Wakes up threads waiting for room in the channel, so they notice `stop` promptly
+        self.hits.close();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Hands a hit to the claim path, waiting for room if the claim path is behind. Returns `false`
+/// once the grinder has been dropped.
+fn report<T>(sender: &mpsc::Sender<T>, hit: T) -> bool {
+    sender.blocking_send(hit).is_ok()
+}
+
+fn grind_keypairs<S>(sender: mpsc::Sender<Hit<S>>, shared: &Shared, wrap: fn(Keypair) -> S) {
+    let scorer = PrefixScorer::new();
+    let mut tried = 0;
+    while !shared.stop.load(Ordering::Relaxed) {
+        tried += 1;
+        if tried == COUNT_BATCH {
+            shared.keys_tried.fetch_add(tried, Ordering::Relaxed);
+            tried = 0;
+        }
+        let keypair = Keypair::new();
+        let prefix_len = scorer.prefix_len(&keypair.pubkey().to_bytes());
+        if prefix_len < shared.min_prefix_len.load(Ordering::Relaxed) as usize {
+            continue;
+        }
+        let hit = Hit {
+            signer: wrap(keypair),
+            prefix_len,
+        };
+        if !report(&sender, hit) {
+            return;
+        }
+    }
+}
+
+fn grind_points(sender: mpsc::Sender<Hit>, shared: &Shared) {
+    let scorer = PrefixScorer::new();
+    // Candidate i is (base + i)·B, so only the starting point needs a scalar multiplication
+    let base = Scalar::from_bytes_mod_order(rand::random());
+    let mut point = &base * &ED25519_BASEPOINT_TABLE;
+    let mut offset = 0u64;
+    let mut tried = 0;
+    while !shared.stop.load(Ordering::Relaxed) {
+        let pubkey = point.compress().to_bytes();
+        let prefix_len = scorer.prefix_len(&pubkey);
+        if prefix_len >= shared.min_prefix_len.load(Ordering::Relaxed) as usize {
+            let scalar = base + Scalar::from(offset);
+            let public = PublicKey::from_bytes(&pubkey).unwrap();
+            let hit = Hit {
+                signer: GroundSigner::Scalar(ScalarSigner::new(scalar, public)),
+                prefix_len,
+            };
+            if !report(&sender, hit) {
+                return;
+            }
+        }
+        point += ED25519_BASEPOINT_POINT;
+        offset += 1;
+        tried += 1;
+        if tried == COUNT_BATCH {
+            shared.keys_tried.fetch_add(tried, Ordering::Relaxed);
+            tried = 0;
+        }
+    }
+}
+
+/// A nonce whose hash mode challenge has at least the minimum number of leading zero bits.
+pub struct NonceHit {
+    /// Slot whose hash the nonce was solved against.
+    pub slot: u64,
+    pub nonce: u64,
+    pub zero_bits: u32,
+}
+
+/// Grinds hash mode nonces on dedicated OS threads, like [`Grinder`] does for vanity keys. Work is
+/// tied to a recent slot hash, which [`HashGrinder::set_challenge`] swaps out before it expires.
+pub struct HashGrinder {
+    hits: mpsc::Receiver<NonceHit>,
+    shared: Arc<HashShared>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+/// State shared between the hash grinder and its threads.
+struct HashShared {
+    payer: Pubkey,
+    /// The slot and slot hash to grind against.
+    challenge: RwLock<(u64, [u8; 32])>,
+    /// Bumped whenever the challenge changes, so threads only take the lock when they need to.
+    generation: AtomicU64,
+    min_zero_bits: AtomicU8,
+    stop: AtomicBool,
+    hashes_tried: AtomicU64,
+}
+
+impl HashGrinder {
+    pub fn spawn(
+        threads: usize,
+        payer: Pubkey,
+        slot: u64,
+        slot_hash: [u8; 32],
+        min_zero_bits: u8,
+    ) -> Self {
+        let (sender, hits) = mpsc::channel(HIT_BUFFER);
+        let shared = Arc::new(HashShared {
+            payer,
+            challenge: RwLock::new((slot, slot_hash)),
+            generation: AtomicU64::new(0),
+            min_zero_bits: AtomicU8::new(min_zero_bits),
+            stop: AtomicBool::new(false),
+            hashes_tried: AtomicU64::new(0),
+        });
+        let handles = (0..threads.max(1))
+            .map(|_| {
+                let sender = sender.clone();
+                let shared = shared.clone();
+                std::thread::spawn(move || grind_nonces(sender, &shared))
+            })
+            .collect();
+        Self {
+            hits,
+            shared,
+            handles,
+        }
+    }
+
+    /// Moves all threads on to a new slot hash.
+    pub fn set_challenge(&self, slot: u64, slot_hash: [u8; 32]) {
+        *self.shared.challenge.write().unwrap() = (slot, slot_hash);
+        self.shared.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Only nonces with at least this many leading zero bits are reported from now on.
+    pub fn set_min_zero_bits(&self, min_zero_bits: u8) {
+        self.shared
+            .min_zero_bits
+            .store(min_zero_bits, Ordering::Relaxed);
+    }
+
+    /// Total number of hashes computed so far, across all threads.
+    pub fn hashes_tried(&self) -> u64 {
+        self.shared.hashes_tried.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the next nonce that meets the minimum number of zero bits.
+    pub async fn next(&mut self) -> Option<NonceHit> {
+        self.hits.recv().await
+    }
+}
+
+impl Drop for HashGrinder {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        self.hits.close();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn grind_nonces(sender: mpsc::Sender<NonceHit>, shared: &HashShared) {
+    let mut generation = u64::MAX;
+    let (mut slot, mut slot_hash) = (0, [0; 32]);
+    // Threads start at random nonces so they don't repeat each other's work
+    let mut nonce: u64 = rand::random();
+    let mut tried = 0;
+    while !shared.stop.load(Ordering::Relaxed) {
+        // Shared state is only checked once per batch to keep the hot loop to hashing
+        if tried == 0 {
+            let current = shared.generation.load(Ordering::Acquire);
+            if current != generation {
+                generation = current;
+                (slot, slot_hash) = *shared.challenge.read().unwrap();
+            }
+        }
+        tried += 1;
+        if tried == COUNT_BATCH {
+            shared.hashes_tried.fetch_add(tried, Ordering::Relaxed);
+            tried = 0;
+        }
+
+        nonce = nonce.wrapping_add(1);
+        let zero_bits = leading_zero_bits(&challenge_hash(&slot_hash, &shared.payer, nonce));
+        if zero_bits < shared.min_zero_bits.load(Ordering::Relaxed) as u32 {
+            continue;
+        }
+        let hit = NonceHit {
+            slot,
+            nonce,
+            zero_bits,
+        };
+        if !report(&sender, hit) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+    use solana_sdk::transaction::Transaction;
+
+    #[tokio::test]
+    async fn point_addition_hits_sign_valid_transactions() {
+        // One leading 'A' turns up every few dozen keys
+        let mut grinder = Grinder::spawn(GrinderMode::PointAddition, 1, 1);
+        let Hit { signer, prefix_len } = grinder.next().await.unwrap();
+        drop(grinder);
+        assert!(matches!(signer, GroundSigner::Scalar(_)));
+        assert!(prefix_len >= 1);
+        assert!(signer.pubkey().to_string().starts_with('A'));
+
+        let message = b"proof of work";
+        let signature = signer.sign_message(message);
+        assert!(signature.verify(signer.pubkey().as_ref(), message));
+        assert!(!signature.verify(Pubkey::new_unique().as_ref(), message));
+
+        let payer = Keypair::new();
+        let ix = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![AccountMeta::new_readonly(signer.pubkey(), true)],
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer.pubkey()),
+            &[&payer as &dyn Signer, &signer],
+            Hash::new_unique(),
+        );
+        transaction.verify().unwrap();
+    }
+
+    #[tokio::test]
+    async fn full_hit_channel_pauses_grinding() {
+        // Every key is a hit, so the channel fills up right away
+        let mut grinder = Grinder::spawn_keypairs(2, 0);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        // Threads wait for room instead of grinding on and dropping hits, so not even one batch
+        // of keys has been tried
+        assert_eq!(grinder.keys_tried(), 0);
+        for _ in 0..HIT_BUFFER {
+            assert!(grinder.next().await.is_some());
+        }
+        // Dropping the grinder wakes the waiting threads so they can stop
+        drop(grinder);
+    }
+}
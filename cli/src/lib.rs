@@ -0,0 +1,403 @@
+//! Client library for the proof of work faucet. [`FaucetClient`] lists, creates, funds and claims
+//! from faucets, and grinds the vanity keys that claims need. The `devnet-pow` CLI is built on it.
+
+pub mod amount;
+pub mod claims;
+mod client;
+pub mod cluster;
+pub mod grinder;
+pub mod pda;
+pub mod prefix;
+pub mod selection;
+pub mod strategy;
+
+pub use amount::{format_ui_amount, to_ui_amount, UiAmount};
+pub use client::FaucetClient;
+pub use strategy::{SelectionStrategy, Strategy};
+
+use anchor_lang::error::ERROR_CODE_OFFSET;
+use anchor_lang::Discriminator;
+use anchor_lang::InstructionData;
+use anchor_lang::ToAccountMetas;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use borsh::BorshDeserialize;
+use proof_of_work_faucet::{
+    AdaptiveConfig, AirdropClaimed, Difficulty, FaucetCreated, FaucetError, FaucetFunded,
+    FaucetStats, FaucetWithdrawn, Mode, Receipt, RemainingBudget,
+};
+use solana_client::client_error::ClientError;
+use solana_sdk::clock::Clock;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionError;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FaucetMetadata {
+    pub spec_pubkey: Pubkey,
+    pub faucet_pubkey: Pubkey,
+    pub difficulty: u8,
+    pub amount: u64,
+    pub mode: Mode,
+    /// Difficulty a claim must currently meet. Differs from `difficulty` for adaptive faucets.
+    pub effective_difficulty: u8,
+    pub adaptive: Option<AdaptiveConfig>,
+    /// Throughput left in the current cap window. `None` if the faucet is uncapped.
+    pub remaining_budget: Option<RemainingBudget>,
+    /// Mint paid out by token faucets. `None` for SOL faucets.
+    pub mint: Option<Pubkey>,
+    /// Slots before a claim's receipt can be closed. `None` if receipts are permanent.
+    pub receipt_cooldown: Option<u64>,
+    pub stats: FaucetStats,
+}
+
+impl FaucetMetadata {
+    /// Describes the faucet behind a spec account as of `clock`.
+    pub fn new(
+        spec_pubkey: Pubkey,
+        difficulty: &Difficulty,
+        clock: &Clock,
+        program_id: &Pubkey,
+    ) -> Self {
+        Self {
+            spec_pubkey,
+            faucet_pubkey: pda::source(&spec_pubkey, program_id),
+            difficulty: difficulty.difficulty,
+            amount: difficulty.amount,
+            mode: difficulty.config.mode,
+            effective_difficulty: difficulty.effective_difficulty(clock.unix_timestamp),
+            adaptive: difficulty.config.adaptive,
+            remaining_budget: difficulty.remaining_budget(clock.slot, clock.epoch),
+            mint: difficulty.mint,
+            receipt_cooldown: difficulty.config.receipt_cooldown,
+            stats: difficulty.stats,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MintInfo {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub decimals: u8,
+}
+
+/// Decimals of the reward currency: the mint's for token faucets, SOL's otherwise.
+pub fn reward_decimals(mint_info: Option<&MintInfo>) -> u8 {
+    mint_info.map_or(9, |mint_info| mint_info.decimals)
+}
+
+/// Builds the instruction that claims a vanity airdrop from a SOL or token faucet.
+pub fn airdrop_ix(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    signer: &Pubkey,
+    metadata: &FaucetMetadata,
+    mint_info: Option<&MintInfo>,
+) -> Instruction {
+    let receipt = pda::vanity_receipt(signer, metadata.difficulty, metadata.mint, program_id);
+    match mint_info {
+        Some(mint_info) => Instruction {
+            program_id: *program_id,
+            accounts: proof_of_work_faucet::accounts::AirdropToken {
+                payer: *payer,
+                signer: *signer,
+                receipt,
+                spec: metadata.spec_pubkey,
+                mint: mint_info.mint,
+                source: metadata.faucet_pubkey,
+                destination: get_associated_token_address_with_program_id(
+                    payer,
+                    &mint_info.mint,
+                    &mint_info.token_program,
+                ),
+                token_program: mint_info.token_program,
+                associated_token_program: anchor_spl::associated_token::ID,
+                system_program: solana_sdk::system_program::id(),
+            }
+            .to_account_metas(None),
+            data: proof_of_work_faucet::instruction::AirdropToken {}.data(),
+        },
+        None => Instruction {
+            program_id: *program_id,
+            accounts: proof_of_work_faucet::accounts::Airdrop {
+                payer: *payer,
+                signer: *signer,
+                receipt,
+                spec: metadata.spec_pubkey,
+                source: metadata.faucet_pubkey,
+                system_program: solana_sdk::system_program::id(),
+            }
+            .to_account_metas(None),
+            data: proof_of_work_faucet::instruction::Airdrop {}.data(),
+        },
+    }
+}
+
+/// Builds the instruction that claims a hash airdrop with a nonce solved against `slot`'s hash.
+pub fn hash_airdrop_ix(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    metadata: &FaucetMetadata,
+    slot: u64,
+    nonce: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: proof_of_work_faucet::accounts::HashAirdrop {
+            payer: *payer,
+            receipt: pda::hash_receipt(payer, slot, nonce, metadata.difficulty, program_id),
+            spec: metadata.spec_pubkey,
+            source: metadata.faucet_pubkey,
+            slot_hashes: solana_sdk::sysvar::slot_hashes::id(),
+            system_program: solana_sdk::system_program::id(),
+        }
+        .to_account_metas(None),
+        data: proof_of_work_faucet::instruction::HashAirdrop { slot, nonce }.data(),
+    }
+}
+
+/// Compute units a claim pays priority fees for. Claims don't request a compute unit limit, so they
+/// are billed for the default limit of an instruction.
+pub const CLAIM_COMPUTE_UNITS: u64 = 200_000;
+
+/// Priority fee, in lamports, of `compute_units` at `compute_unit_price` micro-lamports per unit.
+pub fn priority_fee(compute_unit_price: u64, compute_units: u64) -> u64 {
+    let micro_lamports = compute_unit_price as u128 * compute_units as u128;
+    u64::try_from(micro_lamports.div_ceil(1_000_000)).unwrap_or(u64::MAX)
+}
+
+/// What a claim costs its payer in SOL, on top of the proof of work.
+#[derive(Debug, Clone, Copy)]
+pub struct ClaimCost {
+    /// Rent of the receipt a claim from a faucet with a receipt cooldown creates. Counted as spent,
+    /// though all but `empty_receipt_rent` of it can be reclaimed once the cooldown has passed.
+    pub receipt_rent: u64,
+    /// Rent of the empty receipt a claim from a faucet without a receipt cooldown creates.
+    pub empty_receipt_rent: u64,
+    /// Base transaction fee of a claim on its own.
+    pub fee: u64,
+    /// Priority fee of a claim at the compute unit price claims are sent with.
+    pub priority_fee: u64,
+}
+
+impl ClaimCost {
+    /// Lamports a claim from a SOL faucet leaves its payer with after costs. `None` for token
+    /// faucets, whose reward is not in SOL.
+    pub fn net_value(&self, metadata: &FaucetMetadata) -> Option<i64> {
+        if metadata.mint.is_some() {
+            return None;
+        }
+        let receipt_rent = match metadata.receipt_cooldown {
+            Some(_) => self.receipt_rent,
+            None => self.empty_receipt_rent,
+        };
+        let amount = i64::try_from(metadata.amount).unwrap_or(i64::MAX);
+        let cost = receipt_rent + self.fee + self.priority_fee;
+        Some(amount.saturating_sub(i64::try_from(cost).unwrap_or(i64::MAX)))
+    }
+
+    /// Whether a claim from `metadata` nets at least `min_net_lamports`. Token faucets always do,
+    /// since their reward is not in SOL.
+    pub fn clears(&self, metadata: &FaucetMetadata, min_net_lamports: u64) -> bool {
+        !matches!(
+            self.net_value(metadata),
+            Some(net_value) if net_value < i64::try_from(min_net_lamports).unwrap_or(i64::MAX)
+        )
+    }
+}
+
+/// Events emitted by the faucet program.
+pub enum FaucetEvent {
+    Created(FaucetCreated),
+    Claimed(AirdropClaimed),
+    Funded(FaucetFunded),
+    Withdrawn(FaucetWithdrawn),
+}
+
+impl FaucetEvent {
+    /// The spec of the faucet the event is about.
+    pub fn spec(&self) -> Pubkey {
+        match self {
+            FaucetEvent::Created(event) => event.spec,
+            FaucetEvent::Claimed(event) => event.spec,
+            FaucetEvent::Funded(event) => event.spec,
+            FaucetEvent::Withdrawn(event) => event.spec,
+        }
+    }
+}
+
+/// A faucet event along with the transaction that emitted it.
+pub struct HistoryEntry {
+    pub slot: u64,
+    pub signature: Signature,
+    pub event: FaucetEvent,
+}
+
+/// A receipt account of the payer's, with the lamports it holds.
+pub struct ReceiptAccount {
+    pub pubkey: Pubkey,
+    pub receipt: Receipt,
+    pub lamports: u64,
+}
+
+/// Decodes the faucet events that `emit!` wrote to a transaction's logs.
+pub fn parse_events(logs: &[String]) -> Vec<FaucetEvent> {
+    logs.iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .filter_map(|data| base64::decode(data).ok())
+        .filter_map(|data| {
+            if data.len() < 8 {
+                return None;
+            }
+            let (discriminator, mut event) = data.split_at(8);
+            if discriminator == FaucetCreated::DISCRIMINATOR {
+                FaucetCreated::deserialize(&mut event)
+                    .ok()
+                    .map(FaucetEvent::Created)
+            } else if discriminator == AirdropClaimed::DISCRIMINATOR {
+                AirdropClaimed::deserialize(&mut event)
+                    .ok()
+                    .map(FaucetEvent::Claimed)
+            } else if discriminator == FaucetFunded::DISCRIMINATOR {
+                FaucetFunded::deserialize(&mut event)
+                    .ok()
+                    .map(FaucetEvent::Funded)
+            } else if discriminator == FaucetWithdrawn::DISCRIMINATOR {
+                FaucetWithdrawn::deserialize(&mut event)
+                    .ok()
+                    .map(FaucetEvent::Withdrawn)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Extracts the program's error code from a failed airdrop, if the program rejected it.
+pub fn decode_faucet_error(error: &ClientError) -> Option<FaucetError> {
+    faucet_error(&error.get_transaction_error()?)
+}
+
+/// Maps a failed transaction to the program error that caused it.
+pub fn faucet_error(error: &TransactionError) -> Option<FaucetError> {
+    match error {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => code
+            .checked_sub(ERROR_CODE_OFFSET)
+            .and_then(|index| FaucetError::try_from(index).ok()),
+        _ => None,
+    }
+}
+
+/// A SOL vanity faucet paying `amount` at `difficulty`, for tests.
+#[cfg(test)]
+pub(crate) fn test_faucet(difficulty: u8, amount: u64) -> FaucetMetadata {
+    FaucetMetadata {
+        spec_pubkey: Pubkey::new_unique(),
+        difficulty,
+        effective_difficulty: difficulty,
+        amount,
+        ..FaucetMetadata::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COST: ClaimCost = ClaimCost {
+        receipt_rent: 1_000_000,
+        empty_receipt_rent: 890_880,
+        fee: 5_000,
+        priority_fee: 0,
+    };
+
+    fn faucet(amount: u64, receipt_cooldown: Option<u64>) -> FaucetMetadata {
+        FaucetMetadata {
+            amount,
+            receipt_cooldown,
+            ..FaucetMetadata::default()
+        }
+    }
+
+    #[test]
+    fn nets_reward_after_costs() {
+        assert_eq!(
+            COST.net_value(&faucet(100_000_000, None)),
+            Some(100_000_000 - 890_880 - 5_000)
+        );
+        assert_eq!(
+            COST.net_value(&faucet(100_000_000, Some(1_000))),
+            Some(100_000_000 - 1_000_000 - 5_000)
+        );
+        // A reward smaller than the receipt rent loses SOL
+        assert_eq!(
+            COST.net_value(&faucet(500_000, None)),
+            Some(500_000 - 890_880 - 5_000)
+        );
+        let token_faucet = FaucetMetadata {
+            mint: Some(Pubkey::new_unique()),
+            ..faucet(1, None)
+        };
+        assert_eq!(COST.net_value(&token_faucet), None);
+    }
+
+    #[test]
+    fn filters_by_min_net_lamports() {
+        // Nets -5_000 lamports: the receipt rent and fee eat the whole reward
+        let losing = faucet(1_000_000, Some(1_000));
+        let break_even = faucet(1_005_000, Some(1_000));
+        let profitable = faucet(1_005_001, Some(1_000));
+
+        // By default, only faucets that cost more than they pay are skipped
+        assert!(!COST.clears(&losing, 0));
+        assert!(COST.clears(&break_even, 0));
+        assert!(COST.clears(&profitable, 0));
+        // A higher floor also skips faucets that net less than it
+        assert!(!COST.clears(&break_even, 1));
+        assert!(COST.clears(&profitable, 1));
+
+        let token_faucet = FaucetMetadata {
+            mint: Some(Pubkey::new_unique()),
+            ..losing
+        };
+        assert!(COST.clears(&token_faucet, u64::MAX));
+    }
+
+    #[test]
+    fn prices_priority_fees() {
+        assert_eq!(priority_fee(0, CLAIM_COMPUTE_UNITS), 0);
+        // Partial lamports round up, since the runtime charges them in full
+        assert_eq!(priority_fee(1, CLAIM_COMPUTE_UNITS), 1);
+        assert_eq!(priority_fee(10_000, CLAIM_COMPUTE_UNITS), 2_000);
+        // The product of price and units can overflow a u64 without the fee doing so
+        assert_eq!(priority_fee(u64::MAX, CLAIM_COMPUTE_UNITS), u64::MAX / 5);
+        assert_eq!(priority_fee(u64::MAX, u64::MAX), u64::MAX);
+
+        let cost = ClaimCost {
+            priority_fee: priority_fee(10_000, CLAIM_COMPUTE_UNITS),
+            ..COST
+        };
+        assert_eq!(
+            cost.net_value(&faucet(100_000_000, None)),
+            Some(100_000_000 - 890_880 - 5_000 - 2_000)
+        );
+        assert!(COST.clears(&faucet(897_880, None), 2_000));
+        assert!(!cost.clears(&faucet(897_880, None), 2_000));
+    }
+
+    #[test]
+    fn decodes_every_faucet_error() {
+        let custom = |code| TransactionError::InstructionError(0, InstructionError::Custom(code));
+        for index in 0.. {
+            let Ok(error) = FaucetError::try_from(index) else {
+                break;
+            };
+            let decoded = faucet_error(&custom(u32::from(error))).unwrap();
+            assert_eq!(decoded as u32, index);
+        }
+        assert!(faucet_error(&custom(ERROR_CODE_OFFSET - 1)).is_none());
+        assert!(faucet_error(&custom(0)).is_none());
+        assert!(faucet_error(&TransactionError::AccountNotFound).is_none());
+    }
+}
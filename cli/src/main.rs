@@ -1,29 +1,54 @@
-use std::collections::BTreeMap;
+mod output;
+mod progress;
 
-use anchor_lang::InstructionData;
-use anchor_lang::ToAccountMetas;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
 use anyhow::anyhow;
-use borsh::BorshDeserialize;
-use bs58::encode;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use devnet_pow::claims::{lookup_tables_for, ClaimPool};
+use devnet_pow::cluster::Cluster;
+use devnet_pow::grinder::{Grinder, GrinderMode, GroundSigner, HashGrinder, Hit, NonceHit};
+use devnet_pow::prefix::PrefixScorer;
+use devnet_pow::selection::{
+    get_inferred_faucets, min_difficulty, qualifying_faucets, refresh_faucet, remove_faucet,
+};
+use devnet_pow::{
+    airdrop_ix, decode_faucet_error, faucet_error, format_ui_amount, hash_airdrop_ix, pda,
+    reward_decimals, to_ui_amount, FaucetClient, FaucetEvent, FaucetMetadata, HistoryEntry,
+    MintInfo, SelectionStrategy, Strategy, UiAmount,
+};
 use itertools::Itertools;
-use proof_of_work_faucet::Difficulty;
-use solana_account_decoder::UiAccountEncoding;
+use output::{
+    info, ClaimEvent, CreateRecord, FaucetRecord, GroundKeyRecord, HistoryEvent, HistoryRecord,
+    OutputFormat, ReclaimRecord, TransactionRecord,
+};
+use progress::Progress;
+use proof_of_work_faucet::{
+    AdaptiveConfig, CapWindow, FaucetConfig, FaucetError, FaucetStats, Mode, PayoutPolicy,
+    RemainingBudget, ThroughputCap,
+};
 use solana_cli_config::{Config, ConfigInput, CONFIG_FILE};
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_client::rpc_config::RpcAccountInfoConfig;
-use solana_client::rpc_config::RpcProgramAccountsConfig;
-use solana_client::rpc_filter::RpcFilterType;
 use solana_sdk::commitment_config::CommitmentConfig;
-use solana_sdk::instruction::Instruction;
+use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::read_keypair_file;
+use solana_sdk::signature::Signature;
+use solana_sdk::signature::{read_keypair_file, write_keypair_file};
 use solana_sdk::signer::keypair::Keypair;
 use solana_sdk::signer::Signer;
+use solana_sdk::slot_hashes::SlotHashes;
+use solana_sdk::sysvar;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::available_parallelism;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
 
 pub fn get_network(network_str: &str) -> &str {
     match network_str {
         "devnet" | "dev" | "d" => "https://api.devnet.solana.com",
+        "testnet" | "test" | "t" => "https://api.testnet.solana.com",
         "mainnet" | "main" | "m" | "mainnet-beta" => "https://api.mainnet-beta.solana.com",
         "localnet" | "localhost" | "l" | "local" => "http://localhost:8899",
         _ => network_str,
@@ -34,6 +59,40 @@ pub fn get_payer_keypair_from_path(path: &str) -> anyhow::Result<Keypair> {
     read_keypair_file(&*shellexpand::tilde(path)).map_err(|e| anyhow!(e.to_string()))
 }
 
+/// How often `mine` prints its status line.
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Most claim transactions `mine` keeps in flight before it stops taking new hits.
+const MAX_IN_FLIGHT_CLAIMS: usize = 16;
+
+/// How often `mine` checks whether its in-flight claims have confirmed.
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of `close_receipt` instructions packed into each `reclaim-receipts` transaction.
+const RECEIPTS_PER_TRANSACTION: usize = 10;
+
+/// How long to grind against a slot hash before fetching a fresh one. The SlotHashes sysvar only
+/// remembers the last 512 slots, so claims must land well within ~3 minutes of the challenge.
+const CHALLENGE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum ModeArg {
+    /// Grind signer keypairs whose pubkey starts with base58 'A's
+    #[default]
+    Vanity,
+    /// Grind nonces whose hash with a recent slot hash has leading zero bits
+    Hash,
+}
+
+impl From<ModeArg> for Mode {
+    fn from(mode: ModeArg) -> Self {
+        match mode {
+            ModeArg::Vanity => Mode::Vanity,
+            ModeArg::Hash => Mode::Hash,
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[clap(version, about)]
 struct Arguments {
@@ -42,216 +101,612 @@ struct Arguments {
     /// Optionally include your keypair path. Defaults to your Solana CLI config file.
     #[clap(global = true, short, long)]
     keypair_path: Option<String>,
-    /// Optionally include your RPC endpoint. Use "local", "dev", "main" for default endpoints. Defaults to your Solana CLI config file.
+    /// Optionally include your RPC endpoint. Use "local", "dev", "test", "main" for default endpoints. Defaults to your Solana CLI config file.
     #[clap(global = true, short, long)]
     url: Option<String>,
     /// Optionally include a commitment level. Defaults to your Solana CLI config file.
     #[clap(global = true, short, long)]
     commitment: Option<String>,
+    /// Genesis hash of a custom cluster to allow, besides devnet, testnet and localnet. Mainnet is never allowed
+    #[clap(global = true, long = "allow-cluster", value_name = "GENESIS_HASH")]
+    allow_cluster: Vec<Hash>,
+    /// Address of the faucet program. Defaults to the program ID of the cluster the RPC endpoint serves
+    #[clap(global = true, long, value_name = "PROGRAM_ID")]
+    program_id: Option<Pubkey>,
+    /// Priority fee to pay on every transaction, in micro-lamports per compute unit. Claim costs
+    /// include it
+    #[clap(
+        global = true,
+        long,
+        value_name = "MICRO_LAMPORTS",
+        default_value_t = 0
+    )]
+    with_compute_unit_price: u64,
+    /// Output format. JSON formats print records to stdout and messages to stderr
+    #[clap(global = true, long, value_enum, default_value_t)]
+    output: OutputFormat,
+}
+
+/// The reward a faucet pays per claim. Together with the difficulty, mode and mint it identifies
+/// the faucet, so it has to match to the lamport.
+#[derive(Args, Debug)]
+struct RewardArg {
+    /// Reward amount in SOL (or tokens)
+    #[clap(long, required_unless_present = "reward_lamports")]
+    reward: Option<UiAmount>,
+    /// Reward amount in lamports (or token base units)
+    #[clap(long, conflicts_with = "reward")]
+    reward_lamports: Option<u64>,
+}
+
+impl RewardArg {
+    fn to_base_units(&self, decimals: u8) -> anyhow::Result<u64> {
+        reward_base_units(self.reward.as_ref(), self.reward_lamports, decimals)?
+            .ok_or_else(|| anyhow!("Must specify --reward or --reward-lamports"))
+    }
+}
+
+fn reward_base_units(
+    reward: Option<&UiAmount>,
+    reward_lamports: Option<u64>,
+    decimals: u8,
+) -> anyhow::Result<Option<u64>> {
+    match (reward, reward_lamports) {
+        (_, Some(reward_lamports)) => Ok(Some(reward_lamports)),
+        (Some(reward), None) => Ok(Some(reward.to_base_units(decimals)?)),
+        (None, None) => Ok(None),
+    }
 }
 
 #[derive(Subcommand, Debug)]
 enum SubCommand {
+    #[clap(flatten)]
+    Rpc(RpcCommand),
+    /// Grind signer keypairs offline and save them for claiming later. Needs no RPC access
+    Grind {
+        /// Minimum number of leading 'A's
+        #[clap(short, long)]
+        difficulty: u8,
+        /// Number of keypairs to grind
+        #[clap(long, default_value = "1")]
+        count: usize,
+        /// Directory to write the keypair files to
+        #[clap(long)]
+        out_dir: PathBuf,
+        /// Number of grinding threads. Defaults to the number of cores
+        #[clap(long)]
+        threads: Option<usize>,
+        /// Do not print the periodic hashrate and progress line
+        #[clap(short, long, default_value = "false")]
+        quiet: bool,
+    },
+}
+
+/// Subcommands that talk to a cluster.
+#[derive(Subcommand, Debug)]
+enum RpcCommand {
     /// Creates a proof of work faucet on devnet
     Create {
-        /// Prefix length
+        /// Prefix length, or leading zero bits in hash mode
         #[clap(short, long)]
         difficulty: u8,
-        /// Reward amount in SOL
+        #[clap(flatten)]
+        reward: RewardArg,
+        /// Proof of work mode
+        #[clap(long, value_enum, default_value_t)]
+        mode: ModeArg,
+        /// Create a token faucet for this mint instead of a SOL faucet. The reward is in tokens
+        #[clap(long)]
+        mint: Option<Pubkey>,
+        /// Make the difficulty adaptive, retargeting towards this many claims per hour
+        #[clap(long)]
+        target_claims_per_hour: Option<u32>,
+        /// Length of a retarget window in seconds
+        #[clap(long, default_value = "3600")]
+        retarget_window: i64,
+        /// Lowest difficulty an adaptive faucet can retarget to
+        #[clap(long, default_value = "1")]
+        min_difficulty: u8,
+        /// Highest difficulty an adaptive faucet can retarget to
+        #[clap(long, default_value = "255")]
+        max_difficulty: u8,
+        /// Cap the SOL (or tokens) paid out per throughput window
+        #[clap(long)]
+        max_sol_per_window: Option<UiAmount>,
+        /// Cap the number of claims per throughput window
+        #[clap(long)]
+        max_claims_per_window: Option<u32>,
+        /// Length of a throughput window in slots. Defaults to one epoch
+        #[clap(long)]
+        cap_window_slots: Option<u64>,
+        /// Pay out the remaining balance when the faucet holds less than the reward, instead of
+        /// rejecting the claim
+        #[clap(long, default_value = "false")]
+        allow_partial_payouts: bool,
+        /// Let claimants close their receipts for rent this many slots after claiming. Must be at
+        /// least 1. Closing a vanity receipt lets the same key claim from this faucet again
         #[clap(long)]
-        reward: f64,
+        receipt_cooldown_slots: Option<u64>,
     },
     /// Get all faucets
     GetAllFaucets,
     /// Get faucet address and balance
     GetFaucet {
-        /// Prefix length
+        /// Prefix length, or leading zero bits in hash mode
         #[clap(short, long)]
         difficulty: u8,
-        /// Reward amount in SOL
+        #[clap(flatten)]
+        reward: RewardArg,
+        /// Proof of work mode
+        #[clap(long, value_enum, default_value_t)]
+        mode: ModeArg,
+        /// Mint of a token faucet. The reward is in tokens
         #[clap(long)]
-        reward: f64,
+        mint: Option<Pubkey>,
     },
     /// Mine for SOL
     Mine {
-        /// Prefix length
+        /// Prefix length, or leading zero bits in hash mode
         #[clap(short, long)]
         difficulty: Option<u8>,
+        /// Reward amount in SOL (or tokens)
         #[clap(long)]
-        /// Reward amount in SOL
-        reward: Option<f64>,
-        /// Target number of lamports to mine for
+        reward: Option<UiAmount>,
+        /// Reward amount in lamports (or token base units)
+        #[clap(long, conflicts_with = "reward")]
+        reward_lamports: Option<u64>,
+        /// Target number of lamports (or token base units) to mine for
         #[clap(short, long, default_value = "10000000000")]
         target_lamports: u64,
         /// Do not search for faucets automatically
         #[clap(long, default_value = "false")]
         no_infer: bool,
+        /// Skip SOL faucets whose reward, net of receipt rent and fees, is below this many lamports
+        #[clap(long, default_value = "0")]
+        min_net_lamports: u64,
+        /// How to choose between the faucets a key qualifies for
+        #[clap(long, value_enum, default_value_t)]
+        strategy: SelectionStrategy,
+        /// Proof of work mode
+        #[clap(long, value_enum, default_value_t)]
+        mode: ModeArg,
+        /// Mine tokens from faucets for this mint instead of SOL. The reward is in tokens
+        #[clap(long)]
+        mint: Option<Pubkey>,
+        /// Number of grinding threads. Defaults to the number of cores
+        #[clap(long)]
+        threads: Option<usize>,
+        /// How candidate keys are generated
+        #[clap(long, value_enum, default_value_t)]
+        grinder: GrinderMode,
+        /// Do not print the periodic hashrate and progress line
+        #[clap(short, long, default_value = "false")]
+        quiet: bool,
+    },
+    /// Withdraw SOL from a faucet you created
+    Withdraw {
+        /// Prefix length, or leading zero bits in hash mode
+        #[clap(short, long)]
+        difficulty: u8,
+        #[clap(flatten)]
+        reward: RewardArg,
+        /// Proof of work mode
+        #[clap(long, value_enum, default_value_t)]
+        mode: ModeArg,
+        /// Mint of a token faucet. The reward and amount are in tokens
+        #[clap(long)]
+        mint: Option<Pubkey>,
+        /// Amount to withdraw in SOL. Defaults to the entire faucet balance
+        #[clap(long)]
+        amount: Option<UiAmount>,
+    },
+    /// Close a faucet you created and reclaim its remaining balance
+    Close {
+        /// Prefix length, or leading zero bits in hash mode
+        #[clap(short, long)]
+        difficulty: u8,
+        #[clap(flatten)]
+        reward: RewardArg,
+        /// Proof of work mode
+        #[clap(long, value_enum, default_value_t)]
+        mode: ModeArg,
+        /// Mint of a token faucet. The reward is in tokens
+        #[clap(long)]
+        mint: Option<Pubkey>,
+    },
+    /// Deposit SOL (or tokens) into a faucet
+    Fund {
+        /// Prefix length, or leading zero bits in hash mode
+        #[clap(short, long)]
+        difficulty: u8,
+        #[clap(flatten)]
+        reward: RewardArg,
+        /// Proof of work mode
+        #[clap(long, value_enum, default_value_t)]
+        mode: ModeArg,
+        /// Mint of a token faucet. The reward and amount are in tokens
+        #[clap(long)]
+        mint: Option<Pubkey>,
+        /// Amount to deposit in SOL
+        #[clap(long)]
+        amount: UiAmount,
+    },
+    /// Show the recent creates, claims, deposits and withdrawals of a faucet
+    History {
+        /// Prefix length, or leading zero bits in hash mode
+        #[clap(short, long)]
+        difficulty: u8,
+        #[clap(flatten)]
+        reward: RewardArg,
+        /// Proof of work mode
+        #[clap(long, value_enum, default_value_t)]
+        mode: ModeArg,
+        /// Mint of a token faucet. The reward is in tokens
+        #[clap(long)]
+        mint: Option<Pubkey>,
+        /// Maximum number of transactions to look back through
+        #[clap(long, default_value = "100")]
+        limit: usize,
+    },
+    /// Close your airdrop receipts whose cooldown has passed and reclaim their rent
+    ReclaimReceipts,
+    /// Claim airdrops with signer keypairs saved by `grind`
+    Claim {
+        /// Directory of keypair files. Spent keys are moved to its `claimed` subdirectory
+        #[clap(long)]
+        keys: PathBuf,
+        /// Claim tokens from faucets for this mint instead of SOL
+        #[clap(long)]
+        mint: Option<Pubkey>,
+        /// Skip SOL faucets whose reward, net of receipt rent and fees, is below this many lamports
+        #[clap(long, default_value = "0")]
+        min_net_lamports: u64,
+        /// How to choose between the faucets a key qualifies for
+        #[clap(long, value_enum, default_value_t)]
+        strategy: SelectionStrategy,
     },
-}
-
-#[derive(Debug, Clone, Copy, Default)]
-pub struct FaucetMetadata {
-    pub spec_pubkey: Pubkey,
-    pub faucet_pubkey: Pubkey,
-    pub difficulty: u8,
-    pub amount: u64,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Arguments::parse();
+    let Arguments {
+        subcommand,
+        keypair_path,
+        url,
+        commitment,
+        allow_cluster,
+        program_id,
+        with_compute_unit_price,
+        output,
+    } = Arguments::parse();
+
+    let command = match subcommand {
+        SubCommand::Rpc(command) => command,
+        // Grinding is offline, so it skips the config, keypair and RPC setup below
+        SubCommand::Grind {
+            difficulty,
+            count,
+            out_dir,
+            threads,
+            quiet,
+        } => return grind(difficulty, count, &out_dir, threads, quiet, output).await,
+    };
+
     let config = match CONFIG_FILE.as_ref() {
         Some(config_file) => Config::load(config_file).unwrap_or_else(|_| {
-            println!("Failed to load config file: {}", config_file);
+            info!(output, "Failed to load config file: {}", config_file);
             Config::default()
         }),
         None => Config::default(),
     };
     let commitment =
-        ConfigInput::compute_commitment_config("", &cli.commitment.unwrap_or(config.commitment)).1;
-    let payer = get_payer_keypair_from_path(&cli.keypair_path.unwrap_or(config.keypair_path))?;
-    let network_url = &get_network(&cli.url.unwrap_or(config.json_rpc_url)).to_string();
-    let client = RpcClient::new_with_commitment(network_url.to_string(), commitment);
+        ConfigInput::compute_commitment_config("", &commitment.unwrap_or(config.commitment)).1;
+    let payer = get_payer_keypair_from_path(&keypair_path.unwrap_or(config.keypair_path))?;
+    let network_url = &get_network(&url.unwrap_or(config.json_rpc_url)).to_string();
+    let rpc = RpcClient::new_with_commitment(network_url.to_string(), commitment);
 
-    let genesis = client.get_genesis_hash().await?;
+    let genesis = rpc.get_genesis_hash().await?;
+    let cluster = Cluster::identify(&genesis, network_url, &allow_cluster)?;
+    let program_id = program_id.unwrap_or(cluster.program_id);
+    let mut faucet_client = FaucetClient::new(rpc, program_id);
+    faucet_client.set_compute_unit_price(with_compute_unit_price);
+    let client = faucet_client.rpc();
 
-    match genesis.to_string().as_str() {
-        "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG" => {}
-        _ => anyhow::bail!("Genesis hash does not corespond to devnet"),
-    };
-
-    match cli.subcommand {
-        SubCommand::Create { difficulty, reward } => {
-            let amount: u64 = (reward * 1e9) as u64;
-            let create_spec_data =
-                proof_of_work_faucet::instruction::Create { difficulty, amount }.data();
-            let (spec, _) = Pubkey::find_program_address(
-                &[
-                    b"spec",
-                    difficulty.to_le_bytes().as_ref(),
-                    amount.to_le_bytes().as_ref(),
-                ],
-                &proof_of_work_faucet::id(),
-            );
-            let (faucet, _) = Pubkey::find_program_address(
-                &[b"source", spec.as_ref()],
-                &proof_of_work_faucet::id(),
-            );
+    match command {
+        RpcCommand::Create {
+            difficulty,
+            reward,
+            mode,
+            target_claims_per_hour,
+            retarget_window,
+            min_difficulty,
+            max_difficulty,
+            mint,
+            max_sol_per_window,
+            max_claims_per_window,
+            cap_window_slots,
+            allow_partial_payouts,
+            receipt_cooldown_slots,
+        } => {
+            let mint_info = match mint {
+                Some(mint) => Some(faucet_client.mint_info(&mint).await?),
+                None => None,
+            };
+            let decimals = reward_decimals(mint_info.as_ref());
+            let amount = reward.to_base_units(decimals)?;
+            let max_lamports = max_sol_per_window
+                .map(|max| max.to_base_units(decimals))
+                .transpose()?;
+            let config = FaucetConfig {
+                mode: mode.into(),
+                adaptive: target_claims_per_hour.map(|target_claims_per_hour| AdaptiveConfig {
+                    target_claims_per_hour,
+                    window: retarget_window,
+                    min_difficulty,
+                    max_difficulty,
+                }),
+                cap: if max_lamports.is_some() || max_claims_per_window.is_some() {
+                    Some(ThroughputCap {
+                        window: cap_window_slots.map_or(CapWindow::Epoch, CapWindow::Slots),
+                        max_lamports,
+                        max_claims: max_claims_per_window,
+                    })
+                } else {
+                    None
+                },
+                payout: if allow_partial_payouts {
+                    PayoutPolicy::Partial
+                } else {
+                    PayoutPolicy::FullAmount
+                },
+                receipt_cooldown: receipt_cooldown_slots,
+            };
+            let spec = pda::spec(difficulty, amount, config.mode, mint, &program_id);
+            let faucet = pda::source(&spec, &program_id);
+            let record = |signature: Option<Signature>| CreateRecord {
+                signature: signature.map(|signature| signature.to_string()),
+                spec: spec.to_string(),
+                faucet: faucet.to_string(),
+                authority: payer.pubkey().to_string(),
+                mode: output::mode_name(mode.into()),
+                mint: mint.map(|mint| mint.to_string()),
+                difficulty,
+                amount,
+                reward: to_ui_amount(amount, decimals),
+            };
             if client.get_account(&spec).await.is_ok() {
+                if output.is_json() {
+                    return output.print(&record(None));
+                }
                 println!("Faucet already exists at {}", faucet);
                 return Ok(());
             }
-            let create_accounts = proof_of_work_faucet::accounts::Create {
-                payer: payer.pubkey(),
-                spec,
-                system_program: solana_sdk::system_program::id(),
-            };
-
-            let ix = Instruction {
-                program_id: proof_of_work_faucet::id(),
-                accounts: create_accounts.to_account_metas(None),
-                data: create_spec_data,
-            };
-
-            let transaction = solana_sdk::transaction::Transaction::new_signed_with_payer(
-                &[ix],
-                Some(&payer.pubkey()),
-                &[&payer],
-                client.get_latest_blockhash().await?,
-            );
-
-            let txid = client.send_and_confirm_transaction(&transaction).await?;
-            println!(
-                "Created proof of work faucet with difficulty {} and reward of {} SOL: {}",
-                difficulty, reward, txid
-            );
+            let txid = faucet_client
+                .create(&payer, difficulty, amount, config, mint_info.as_ref())
+                .await?;
+            if output.is_json() {
+                return output.print(&record(Some(txid)));
+            }
+            match mint {
+                Some(mint) => println!(
+                    "Created {:?} proof of work faucet with difficulty {} and reward of {} {} tokens: {}",
+                    config.mode, difficulty, format_ui_amount(amount, decimals), mint, txid
+                ),
+                None => println!(
+                    "Created {:?} proof of work faucet with difficulty {} and reward of {} SOL: {}",
+                    config.mode, difficulty, format_ui_amount(amount, decimals), txid
+                ),
+            }
             println!("Faucet spec address: {}", spec);
             println!("Faucet address: {}", faucet);
+            println!("Faucet authority: {}", payer.pubkey());
             Ok(())
         }
-        SubCommand::GetAllFaucets => {
-            for FaucetMetadata {
-                faucet_pubkey,
-                difficulty,
-                amount,
-                ..
-            } in get_all_faucets(&client, &commitment).await?.iter()
-            {
-                let reward = *amount as f64 / 1e9;
-                let balance = client
-                    .get_balance_with_commitment(faucet_pubkey, commitment)
-                    .await?
-                    .value;
+        RpcCommand::GetAllFaucets => {
+            let faucets = faucet_client.list().await?;
+            let balances = faucet_client.balances(&faucets).await?;
+            let vanity_cost = faucet_client.claim_cost(Mode::Vanity).await?;
+            let hash_cost = faucet_client.claim_cost(Mode::Hash).await?;
+            let mints = faucets
+                .iter()
+                .filter_map(|metadata| metadata.mint)
+                .unique()
+                .collect_vec();
+            let mint_decimals = mints
+                .iter()
+                .copied()
+                .zip(faucet_client.mint_infos(&mints).await?)
+                .map(|(mint, mint_info)| (mint, mint_info.decimals))
+                .collect::<BTreeMap<_, _>>();
+            let mut records = vec![];
+            for (metadata, balance) in faucets.iter().zip(balances) {
+                let FaucetMetadata {
+                    faucet_pubkey,
+                    difficulty,
+                    amount,
+                    mode,
+                    effective_difficulty,
+                    adaptive,
+                    remaining_budget,
+                    mint,
+                    stats,
+                    ..
+                } = metadata;
+                let decimals = mint.map_or(9, |mint| mint_decimals[&mint]);
+                let net_value = match mode {
+                    Mode::Vanity => vanity_cost.net_value(metadata),
+                    Mode::Hash => hash_cost.net_value(metadata),
+                };
+                if output.is_json() {
+                    records.push(FaucetRecord::new(
+                        metadata, true, balance, decimals, net_value,
+                    ));
+                    continue;
+                }
+                let reward = format_ui_amount(*amount, decimals);
+                let currency = match mint {
+                    Some(mint) => format!("{} tokens", mint),
+                    None => "SOL".to_string(),
+                };
                 println!("Faucet address: {}", faucet_pubkey);
-                println!("Faucet balance: {} SOL", balance as f64 / 1e9);
-                println!("Difficulty: {}", difficulty);
+                println!(
+                    "Faucet balance: {} {}",
+                    format_ui_amount(balance, decimals),
+                    currency
+                );
+                println!("Mode: {:?}", mode);
+                println!("Difficulty: {}", effective_difficulty);
+                if let Some(adaptive) = adaptive {
+                    println!(
+                        "Adaptive: targets {} claims per hour, seed difficulty {}",
+                        adaptive.target_claims_per_hour, difficulty
+                    );
+                }
                 println!("Reward: {}", reward);
+                if let Some(net_value) = net_value {
+                    println!("Net value per claim: {} SOL", format_net_value(net_value));
+                }
+                if let Some(RemainingBudget { lamports, claims }) = *remaining_budget {
+                    if let Some(lamports) = lamports {
+                        println!(
+                            "Remaining budget this window: {} {}",
+                            format_ui_amount(lamports, decimals),
+                            currency
+                        );
+                    }
+                    if let Some(claims) = claims {
+                        println!("Remaining claims this window: {}", claims);
+                    }
+                }
+                print_stats(stats, decimals, &currency);
+                let mode_flag = match mode {
+                    Mode::Vanity => "",
+                    Mode::Hash => " --mode hash",
+                };
+                let mint_flag = match mint {
+                    Some(mint) => format!(" --mint {}", mint),
+                    None => String::new(),
+                };
                 println!(
-                    "Command: devnet-pow mine -d {} --reward {} -ud",
-                    difficulty, reward
+                    "Command: devnet-pow mine -d {} --reward {}{}{} -ud",
+                    effective_difficulty, reward, mode_flag, mint_flag
                 );
                 println!()
             }
+            if output.is_json() {
+                output.print(&records)?;
+            }
             Ok(())
         }
-        SubCommand::GetFaucet { difficulty, reward } => {
-            let amount: u64 = (reward * 1e9) as u64;
-            let (spec, _) = Pubkey::find_program_address(
-                &[
-                    b"spec",
-                    difficulty.to_le_bytes().as_ref(),
-                    amount.to_le_bytes().as_ref(),
-                ],
-                &proof_of_work_faucet::id(),
-            );
-            let (faucet, _) = Pubkey::find_program_address(
-                &[b"source", spec.as_ref()],
-                &proof_of_work_faucet::id(),
-            );
+        RpcCommand::GetFaucet {
+            difficulty,
+            reward,
+            mode,
+            mint,
+        } => {
+            let mint_info = match mint {
+                Some(mint) => Some(faucet_client.mint_info(&mint).await?),
+                None => None,
+            };
+            let decimals = reward_decimals(mint_info.as_ref());
+            let amount = reward.to_base_units(decimals)?;
+            let spec = pda::spec(difficulty, amount, Mode::from(mode), mint, &program_id);
+            let faucet = pda::source(&spec, &program_id);
+            if output.is_json() {
+                let existing = faucet_client.get(&spec).await?;
+                let metadata = match existing {
+                    Some(metadata) => metadata,
+                    None => FaucetMetadata {
+                        spec_pubkey: spec,
+                        faucet_pubkey: faucet,
+                        difficulty,
+                        amount,
+                        mode: mode.into(),
+                        effective_difficulty: difficulty,
+                        mint,
+                        ..FaucetMetadata::default()
+                    },
+                };
+                let balance = faucet_client.balance(&metadata).await?;
+                let net_value = faucet_client
+                    .claim_cost(metadata.mode)
+                    .await?
+                    .net_value(&metadata);
+                let record =
+                    FaucetRecord::new(&metadata, existing.is_some(), balance, decimals, net_value);
+                return output.print(&record);
+            }
             println!("Faucet address: {}", faucet);
 
-            let balance = client
-                .get_balance_with_commitment(&faucet, commitment)
-                .await?
-                .value;
-            println!("Faucet balance: {} SOL", balance as f64 / 1e9);
+            let currency = match mint {
+                Some(mint) => {
+                    let balance = client
+                        .get_token_account_balance_with_commitment(&faucet, commitment)
+                        .await?
+                        .value;
+                    println!(
+                        "Faucet balance: {} {} tokens",
+                        balance.ui_amount_string, mint
+                    );
+                    format!("{} tokens", mint)
+                }
+                None => {
+                    let balance = client
+                        .get_balance_with_commitment(&faucet, commitment)
+                        .await?
+                        .value;
+                    println!("Faucet balance: {} SOL", format_ui_amount(balance, 9));
+                    "SOL".to_string()
+                }
+            };
+
+            // The faucet address can be derived and funded before the spec exists
+            if let Some(metadata) = faucet_client.get(&spec).await? {
+                print_stats(&metadata.stats, decimals, &currency);
+            }
             Ok(())
         }
-        SubCommand::Mine {
+        RpcCommand::Mine {
             difficulty,
             reward,
+            reward_lamports,
             target_lamports,
             no_infer,
+            min_net_lamports,
+            strategy,
+            mode,
+            mint,
+            threads,
+            grinder: grinder_mode,
+            quiet,
         } => {
-            let mut faucet_specs = if no_infer {
+            let mode = Mode::from(mode);
+            if mode == Mode::Hash && mint.is_some() {
+                anyhow::bail!("Token faucets only support vanity mode");
+            }
+            let mint_info = match mint {
+                Some(mint) => Some(faucet_client.mint_info(&mint).await?),
+                None => None,
+            };
+            let decimals = reward_decimals(mint_info.as_ref());
+            let reward = reward_base_units(reward.as_ref(), reward_lamports, decimals)?;
+            let (mut faucet_specs, mut balances) = if no_infer {
                 let mut faucet_specs = BTreeMap::new();
                 match (difficulty, reward) {
                     (Some(d), Some(r)) => {
                         let mut spec = BTreeMap::new();
-                        let reward_as_amount = (r * 1e9) as u64;
-                        let (spec_pubkey, _) = Pubkey::find_program_address(
-                            &[
-                                b"spec",
-                                d.to_le_bytes().as_ref(),
-                                reward_as_amount.to_le_bytes().as_ref(),
-                            ],
-                            &proof_of_work_faucet::id(),
-                        );
-                        let (faucet_pubkey, _) = Pubkey::find_program_address(
-                            &[b"source", spec_pubkey.as_ref()],
-                            &proof_of_work_faucet::id(),
-                        );
+                        let spec_pubkey = pda::spec(d, r, mode, mint, &program_id);
+                        let metadata = faucet_client
+                            .get(&spec_pubkey)
+                            .await?
+                            .ok_or_else(|| anyhow!("Faucet spec {} not found", spec_pubkey))?;
+                        let effective_difficulty = metadata.effective_difficulty;
+                        let balances =
+                            HashMap::from([(spec_pubkey, faucet_client.balance(&metadata).await?)]);
 
-                        let metadata = FaucetMetadata {
-                            spec_pubkey,
-                            faucet_pubkey,
-                            difficulty: d,
-                            amount: reward_as_amount,
-                        };
-
-                        spec.insert(reward_as_amount, metadata);
-                        faucet_specs.insert(d, spec);
-                        faucet_specs
+                        spec.insert(spec_pubkey, metadata);
+                        faucet_specs.insert(effective_difficulty, spec);
+                        (faucet_specs, balances)
                     }
                     _ => {
                         return Err(anyhow!(
@@ -260,10 +715,18 @@ async fn main() -> anyhow::Result<()> {
                     }
                 }
             } else {
-                get_inferred_faucets(&client, &commitment, difficulty, reward).await?
+                get_inferred_faucets(
+                    &faucet_client,
+                    mode,
+                    mint,
+                    difficulty,
+                    reward,
+                    min_net_lamports,
+                )
+                .await?
             };
             if faucet_specs.is_empty() {
-                println!("No faucets found");
+                info!(output, "No faucets found");
                 return Ok(());
             }
 
@@ -274,259 +737,1112 @@ async fn main() -> anyhow::Result<()> {
                     .await?;
             }
 
-            // This variable is used to short circuit the loop if the grinded key is below the minimum prefix length
-            let mut min_prefix_len = *faucet_specs
-                .keys()
-                .min()
+            if mode == Mode::Hash {
+                return mine_hash(
+                    &faucet_client,
+                    &payer,
+                    faucet_specs,
+                    balances,
+                    strategy,
+                    target_lamports,
+                    threads,
+                    quiet,
+                    output,
+                )
+                .await;
+            }
+
+            // This variable is used to short circuit the loop if the grinded key is below the difficulty
+            // the strategy targets
+            let mut min_prefix_len = min_difficulty(&strategy, &faucet_specs, &balances)
                 .ok_or_else(|| anyhow!("No faucets found"))?;
 
-            println!("Minimum difficulty: {}", min_prefix_len);
-            println!("Setup complete! Starting mining process...");
-            println!();
+            let threads = resolve_threads(threads);
+            let mut grinder = Grinder::spawn(grinder_mode, threads, min_prefix_len);
+
+            info!(output, "Minimum difficulty: {}", min_prefix_len);
+            info!(
+                output,
+                "Setup complete! Starting mining process on {} threads...", threads
+            );
+            info!(output, "");
             let mut airdropped_amount = 0;
 
+            let scorer = PrefixScorer::new();
+            let currency = match mint {
+                Some(mint) => format!("{} tokens", mint),
+                None => "SOL".to_string(),
+            };
+            let mut progress = Progress::new("keys", currency, decimals, target_lamports, output);
+            let mut status = tokio::time::interval_at(
+                tokio::time::Instant::now() + STATUS_INTERVAL,
+                STATUS_INTERVAL,
+            );
+            status.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            let mut claims = ClaimPool::new(client, &payer, commitment, MAX_IN_FLIGHT_CLAIMS);
+            claims.set_compute_unit_price(faucet_client.compute_unit_price());
+            // A key that meets every difficulty claims from a faucet at each of them. If claims from
+            // every faucet don't fit in one transaction, look the faucet accounts up from a table
+            let sample_signer = Pubkey::new_unique();
+            let widest_claim = faucet_specs
+                .values()
+                .flat_map(|specs_for_difficulty| specs_for_difficulty.values())
+                .map(|metadata| {
+                    airdrop_ix(
+                        &program_id,
+                        &payer.pubkey(),
+                        &sample_signer,
+                        metadata,
+                        mint_info.as_ref(),
+                    )
+                })
+                .collect_vec();
+            if !claims.fits(&widest_claim) {
+                let mut addresses = vec![solana_sdk::system_program::id()];
+                if let Some(mint_info) = mint_info {
+                    addresses.extend([
+                        mint_info.mint,
+                        mint_info.token_program,
+                        anchor_spl::associated_token::ID,
+                        get_associated_token_address_with_program_id(
+                            &payer.pubkey(),
+                            &mint_info.mint,
+                            &mint_info.token_program,
+                        ),
+                    ]);
+                }
+                addresses.extend(
+                    faucet_specs
+                        .values()
+                        .flat_map(|specs_for_difficulty| specs_for_difficulty.values())
+                        .flat_map(|metadata| [metadata.spec_pubkey, metadata.faucet_pubkey]),
+                );
+                let lookup_tables =
+                    lookup_tables_for(client, &payer, program_id, addresses).await?;
+                info!(
+                    output,
+                    "Claiming through lookup tables {}. Later runs reuse them; close them with `solana address-lookup-table` when you are done",
+                    lookup_tables.iter().map(|table| table.key).join(", ")
+                );
+                for lookup_table in lookup_tables {
+                    claims.use_lookup_table(lookup_table);
+                }
+            }
+            let mut confirmations = tokio::time::interval(CONFIRMATION_POLL_INTERVAL);
+            confirmations.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
             while airdropped_amount < target_lamports {
-                let signer = Keypair::new();
+                grinder.set_min_prefix_len(min_prefix_len);
+                tokio::select! {
+                    hit = grinder.next(), if claims.has_capacity() => {
+                        let Hit { signer, prefix_len } = match hit {
+                            Some(hit) => hit,
+                            None => break,
+                        };
 
-                let prefix_len = encode(signer.pubkey().as_ref())
-                    .into_string()
-                    .chars()
-                    .take_while(|ch| ch == &'A')
-                    .count();
+                        // Hits found before the minimum was raised can still be queued
+                        if prefix_len < min_prefix_len as usize {
+                            continue;
+                        }
 
-                if prefix_len < min_prefix_len as usize {
-                    continue;
-                }
+                        let pending_claims = plan_claims(
+                            &faucet_specs,
+                            &balances,
+                            &strategy,
+                            Arc::new(signer),
+                            prefix_len,
+                        );
+                        let pubkey = match pending_claims.first() {
+                            Some(claim) => claim.signer.pubkey(),
+                            None => continue,
+                        };
+                        info!(output, "Keypair mined! Pubkey: {}: ", pubkey);
 
-                let mut candidate_faucets = vec![];
-                faucet_specs
-                    .iter()
-                    .for_each(|(difficulty, specs_for_difficulty)| {
-                        // Filter the faucets that meet the difficulty requirement
-                        if *difficulty as usize <= prefix_len {
-                            specs_for_difficulty.iter().for_each(|(_, spec)| {
-                                candidate_faucets.push(*spec);
-                            })
+                        // Claims from different faucets are independent, so they share transactions
+                        submit_claims(&mut claims, &program_id, &payer.pubkey(), mint_info.as_ref(), pending_claims).await;
+                    }
+                    _ = confirmations.tick(), if !claims.is_empty() => {
+                        let outcomes = match claims.poll().await {
+                            Ok(outcomes) => outcomes,
+                            Err(e) => {
+                                info!(output, "Failed to check claim status: {}", e);
+                                continue;
+                            }
+                        };
+                        for (claim, outcome) in outcomes {
+                            let metadata = claim.metadata;
+                            let e = match outcome {
+                                Ok(txid) => {
+                                    let reward = format_ui_amount(metadata.amount, decimals);
+                                    if output.is_json() {
+                                        output.print_line(&ClaimEvent::Claimed {
+                                            signature: txid.to_string(),
+                                            signer: claim.signer.pubkey().to_string(),
+                                            spec: metadata.spec_pubkey.to_string(),
+                                            faucet: metadata.faucet_pubkey.to_string(),
+                                            difficulty: metadata.difficulty,
+                                            amount: metadata.amount,
+                                            reward: to_ui_amount(metadata.amount, decimals),
+                                        })?;
+                                    } else {
+                                        match mint {
+                                            Some(mint) => println!(
+                                                "Received {} {} tokens from faucet {}: {}",
+                                                reward, mint, metadata.faucet_pubkey, txid
+                                            ),
+                                            None => println!(
+                                                "Received {} SOL from faucet {}: {}",
+                                                reward, metadata.faucet_pubkey, txid
+                                            ),
+                                        }
+                                    }
+                                    airdropped_amount += metadata.amount;
+                                    // A faucet that is running dry is worth less to the strategy
+                                    if let Some(balance) = balances.get_mut(&metadata.spec_pubkey) {
+                                        *balance = balance.saturating_sub(metadata.amount);
+                                    }
+                                    if let Some(min) = min_difficulty(&strategy, &faucet_specs, &balances) {
+                                        min_prefix_len = min;
+                                    }
+                                    continue;
+                                }
+                                Err(e) => e,
+                            };
+                            if output.is_json() {
+                                output.print_line(&ClaimEvent::Failed {
+                                    error: e.to_string(),
+                                    signer: claim.signer.pubkey().to_string(),
+                                    spec: metadata.spec_pubkey.to_string(),
+                                    faucet: metadata.faucet_pubkey.to_string(),
+                                    difficulty: metadata.difficulty,
+                                })?;
+                            }
+                            // Empty faucets fail preflight, so they are caught here as well
+                            match e.transaction_error().as_ref().and_then(faucet_error) {
+                                Some(FaucetError::AlreadyClaimed) => {
+                                    info!(output,
+                                        "Key {} has already claimed from faucet {}",
+                                        claim.signer.pubkey(),
+                                        metadata.faucet_pubkey
+                                    );
+                                    continue;
+                                }
+                                Some(FaucetError::InsufficientDifficulty) => {
+                                    // The faucet retargeted since we fetched it
+                                    info!(output,
+                                        "Faucet {} raised its difficulty",
+                                        metadata.faucet_pubkey
+                                    );
+                                    refresh_faucet(&faucet_client, &mut faucet_specs, &metadata).await;
+                                }
+                                Some(
+                                    error @ (FaucetError::FaucetEmpty
+                                    | FaucetError::InsufficientFaucetBalance
+                                    | FaucetError::BudgetExceeded),
+                                ) => {
+                                    info!(output,
+                                        "Dropping faucet {}: {}",
+                                        metadata.faucet_pubkey, error
+                                    );
+                                    remove_faucet(&mut faucet_specs, &metadata);
+                                }
+                                _ => info!(output, "Failed to recieve airdrop: {}", e),
+                            };
+                            min_prefix_len = match min_difficulty(&strategy, &faucet_specs, &balances) {
+                                Some(min) => min,
+                                None => {
+                                    info!(output, "No faucets remaining");
+                                    return Ok(());
+                                }
+                            };
                         }
-                    });
-                candidate_faucets.sort_by(|spec1, spec2| {
-                    if spec1.amount != spec2.amount {
-                        spec1.amount.cmp(&spec2.amount)
-                    } else {
-                        spec1.difficulty.cmp(&spec2.difficulty)
                     }
+                    _ = status.tick(), if !quiet => {
+                        progress.report(
+                            grinder.keys_tried(),
+                            airdropped_amount,
+                            faucet_specs.keys().copied(),
+                            |difficulty| scorer.probability(difficulty as usize),
+                        );
+                    }
+                }
+            }
+            Ok(())
+        }
+        RpcCommand::Withdraw {
+            difficulty,
+            reward,
+            mode,
+            mint,
+            amount,
+        } => {
+            let mint_info = match mint {
+                Some(mint) => Some(faucet_client.mint_info(&mint).await?),
+                None => None,
+            };
+            let decimals = reward_decimals(mint_info.as_ref());
+            let reward_as_amount = reward.to_base_units(decimals)?;
+            let spec = pda::spec(
+                difficulty,
+                reward_as_amount,
+                Mode::from(mode),
+                mint,
+                &program_id,
+            );
+            let faucet = pda::source(&spec, &program_id);
+            let amount = amount
+                .map(|a| a.to_base_units(decimals))
+                .transpose()?
+                .unwrap_or(u64::MAX);
+            let txid = faucet_client
+                .withdraw(&payer, &spec, amount, mint_info.as_ref())
+                .await?;
+            if output.is_json() {
+                return output.print(&TransactionRecord {
+                    signature: txid.to_string(),
+                    spec: spec.to_string(),
+                    faucet: faucet.to_string(),
+                });
+            }
+            println!("Withdrew from faucet {}: {}", faucet, txid);
+            Ok(())
+        }
+        RpcCommand::Close {
+            difficulty,
+            reward,
+            mode,
+            mint,
+        } => {
+            let mint_info = match mint {
+                Some(mint) => Some(faucet_client.mint_info(&mint).await?),
+                None => None,
+            };
+            let amount = reward.to_base_units(reward_decimals(mint_info.as_ref()))?;
+            let spec = pda::spec(difficulty, amount, Mode::from(mode), mint, &program_id);
+            let faucet = pda::source(&spec, &program_id);
+            let txid = faucet_client
+                .close(&payer, &spec, mint_info.as_ref())
+                .await?;
+            if output.is_json() {
+                return output.print(&TransactionRecord {
+                    signature: txid.to_string(),
+                    spec: spec.to_string(),
+                    faucet: faucet.to_string(),
                 });
+            }
+            println!("Closed faucet {}: {}", faucet, txid);
+            Ok(())
+        }
+        RpcCommand::Fund {
+            difficulty,
+            reward,
+            mode,
+            mint,
+            amount,
+        } => {
+            let mint_info = match mint {
+                Some(mint) => Some(faucet_client.mint_info(&mint).await?),
+                None => None,
+            };
+            let decimals = reward_decimals(mint_info.as_ref());
+            let reward_as_amount = reward.to_base_units(decimals)?;
+            let spec = pda::spec(
+                difficulty,
+                reward_as_amount,
+                Mode::from(mode),
+                mint,
+                &program_id,
+            );
+            let faucet = pda::source(&spec, &program_id);
+            let amount = amount.to_base_units(decimals)?;
+            let txid = faucet_client
+                .fund(&payer, &spec, amount, mint_info.as_ref())
+                .await?;
+            if output.is_json() {
+                return output.print(&TransactionRecord {
+                    signature: txid.to_string(),
+                    spec: spec.to_string(),
+                    faucet: faucet.to_string(),
+                });
+            }
+            println!("Funded faucet {}: {}", faucet, txid);
+            Ok(())
+        }
+        RpcCommand::History {
+            difficulty,
+            reward,
+            mode,
+            mint,
+            limit,
+        } => {
+            let mint_info = match mint {
+                Some(mint) => Some(faucet_client.mint_info(&mint).await?),
+                None => None,
+            };
+            let decimals = reward_decimals(mint_info.as_ref());
+            let amount = reward.to_base_units(decimals)?;
+            let spec = pda::spec(difficulty, amount, Mode::from(mode), mint, &program_id);
+            let currency = match mint {
+                Some(mint) => format!("{} tokens", mint),
+                None => "SOL".to_string(),
+            };
 
-                if candidate_faucets.is_empty() {
-                    println!("No candidate faucets found for {}", signer.pubkey());
+            for HistoryEntry {
+                slot,
+                signature,
+                event,
+            } in faucet_client.history(&spec, limit).await?
+            {
+                let event = match event {
+                    FaucetEvent::Created(event) => HistoryEvent::Created {
+                        authority: event.authority.to_string(),
+                        difficulty: event.difficulty,
+                        amount: event.amount,
+                    },
+                    FaucetEvent::Claimed(event) => HistoryEvent::Claimed {
+                        payer: event.payer.to_string(),
+                        signer: event.signer.to_string(),
+                        amount: event.amount,
+                        prefix_len: event.prefix_len,
+                    },
+                    FaucetEvent::Funded(event) => HistoryEvent::Funded {
+                        funder: event.funder.to_string(),
+                        amount: event.amount,
+                    },
+                    FaucetEvent::Withdrawn(event) => HistoryEvent::Withdrawn {
+                        authority: event.authority.to_string(),
+                        amount: event.amount,
+                    },
+                };
+                if output.is_json() {
+                    output.print_line(&HistoryRecord {
+                        slot,
+                        signature: signature.to_string(),
+                        event,
+                    })?;
                     continue;
                 }
+                let description = match event {
+                    HistoryEvent::Created {
+                        authority,
+                        difficulty,
+                        amount,
+                    } => format!(
+                        "Created by {} with difficulty {} and reward of {} {}",
+                        authority,
+                        difficulty,
+                        format_ui_amount(amount, decimals),
+                        currency
+                    ),
+                    HistoryEvent::Claimed {
+                        payer,
+                        signer,
+                        amount,
+                        prefix_len,
+                    } => format!(
+                        "{} claimed {} {} with {} (prefix length {})",
+                        payer,
+                        format_ui_amount(amount, decimals),
+                        currency,
+                        signer,
+                        prefix_len
+                    ),
+                    HistoryEvent::Funded { funder, amount } => format!(
+                        "{} deposited {} {}",
+                        funder,
+                        format_ui_amount(amount, decimals),
+                        currency
+                    ),
+                    HistoryEvent::Withdrawn { authority, amount } => format!(
+                        "{} withdrew {} {}",
+                        authority,
+                        format_ui_amount(amount, decimals),
+                        currency
+                    ),
+                };
+                println!("[slot {}] {}: {}", slot, description, signature);
+            }
+            Ok(())
+        }
+        RpcCommand::Claim {
+            keys,
+            mint,
+            min_net_lamports,
+            strategy,
+        } => {
+            let mint_info = match mint {
+                Some(mint) => Some(faucet_client.mint_info(&mint).await?),
+                None => None,
+            };
+            let decimals = reward_decimals(mint_info.as_ref());
+            let currency = match mint {
+                Some(mint) => format!("{} tokens", mint),
+                None => "SOL".to_string(),
+            };
+            let (faucet_specs, mut balances) = get_inferred_faucets(
+                &faucet_client,
+                Mode::Vanity,
+                mint,
+                None,
+                None,
+                min_net_lamports,
+            )
+            .await?;
+            let faucets = faucet_specs
+                .into_values()
+                .flat_map(|specs_for_difficulty| specs_for_difficulty.into_values())
+                .collect_vec();
+            if faucets.is_empty() {
+                info!(output, "No faucets found");
+                return Ok(());
+            }
 
-                println!("Keypair mined! Pubkey: {}: ", signer.pubkey());
-
-                // Keep track of the difficulties that we've mined for the current key
-                let mut matched_difficulties = vec![];
-
-                // Try to claim the airdrop from each of the candidate faucets
-                while !candidate_faucets.is_empty() {
-                    let metadata = candidate_faucets.pop().unwrap();
+            let mut key_paths = vec![];
+            for entry in std::fs::read_dir(&keys)? {
+                let path = entry?.path();
+                if path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|extension| extension == "json")
+                {
+                    key_paths.push(path);
+                }
+            }
+            key_paths.sort();
+            info!(
+                output,
+                "Found {} keypairs in {}",
+                key_paths.len(),
+                keys.display()
+            );
 
-                    if matched_difficulties.contains(&metadata.difficulty) {
-                        continue;
-                    }
+            if !key_paths.is_empty() && client.get_balance(&payer.pubkey()).await? < 5000 {
+                // Try to request airdrop the normal way if the wallet is completely empty
+                client
+                    .request_airdrop(&payer.pubkey(), 1_000_000_000)
+                    .await?;
+            }
 
-                    if client
-                        .get_balance_with_commitment(&metadata.faucet_pubkey, commitment)
-                        .await?
-                        .value
-                        < metadata.amount
-                    {
-                        // Remove this key from the global list of faucets
-                        println!("Faucet {} is empty", metadata.faucet_pubkey);
-                        faucet_specs
-                            .get_mut(&metadata.difficulty)
-                            .unwrap()
-                            .remove(&metadata.amount);
-
-                        // Update min_prefix_len if necessary
-                        if faucet_specs.get(&metadata.difficulty).unwrap().is_empty() {
-                            faucet_specs.remove(&metadata.difficulty);
-                            if metadata.difficulty == min_prefix_len {
-                                min_prefix_len = match faucet_specs.keys().min() {
-                                    Some(min) => *min,
-                                    None => {
-                                        println!("No faucets remaining");
-                                        return Ok(());
-                                    }
-                                };
-                            }
-                        }
+            let scorer = PrefixScorer::new();
+            let claimed_dir = keys.join("claimed");
+            let mut airdropped_amount = 0;
+            for path in key_paths {
+                let signer = match read_keypair_file(&path) {
+                    Ok(signer) => signer,
+                    Err(e) => {
+                        info!(output, "Skipping {}: {}", path.display(), e);
                         continue;
                     }
-
-                    let reward = metadata.amount as f64 / 1e9;
-                    let (receipt, _) = Pubkey::find_program_address(
-                        &[
-                            b"receipt",
-                            signer.pubkey().as_ref(),
-                            metadata.difficulty.to_le_bytes().as_ref(),
-                        ],
-                        &proof_of_work_faucet::id(),
+                };
+                let prefix_len = scorer.prefix_len(&signer.pubkey().to_bytes());
+                let mut candidate_faucets = faucets
+                    .iter()
+                    .filter(|metadata| metadata.effective_difficulty as usize <= prefix_len)
+                    .copied()
+                    .sorted_by(|metadata1, metadata2| {
+                        let value = |metadata: &FaucetMetadata| {
+                            strategy.value(metadata, balances[&metadata.spec_pubkey])
+                        };
+                        value(metadata1).total_cmp(&value(metadata2))
+                    })
+                    .collect_vec();
+                if candidate_faucets.is_empty() {
+                    info!(
+                        output,
+                        "No candidate faucets found for {} (prefix length {})",
+                        signer.pubkey(),
+                        prefix_len
                     );
-                    let airdrop_accounts = proof_of_work_faucet::accounts::Airdrop {
-                        payer: payer.pubkey(),
-                        signer: signer.pubkey(),
-                        receipt,
-                        spec: metadata.spec_pubkey,
-                        source: metadata.faucet_pubkey,
-                        system_program: solana_sdk::system_program::id(),
-                    };
-
-                    let ix = Instruction {
-                        program_id: proof_of_work_faucet::id(),
-                        accounts: airdrop_accounts.to_account_metas(None),
-                        data: proof_of_work_faucet::instruction::Airdrop {}.data(),
-                    };
+                    continue;
+                }
 
-                    let blockhash = match client.get_latest_blockhash().await {
-                        Ok(blockhash) => blockhash,
-                        Err(_) => continue,
-                    };
-                    let transaction = solana_sdk::transaction::Transaction::new_signed_with_payer(
-                        &[ix],
-                        Some(&payer.pubkey()),
-                        &[&payer, &signer],
-                        blockhash,
-                    );
+                // A key gets one receipt per difficulty, so skip difficulties it already claimed at
+                let receipts = candidate_faucets
+                    .iter()
+                    .map(|metadata| {
+                        pda::vanity_receipt(
+                            &signer.pubkey(),
+                            metadata.difficulty,
+                            metadata.mint,
+                            &program_id,
+                        )
+                    })
+                    .collect_vec();
+                let mut claimed_difficulties = candidate_faucets
+                    .iter()
+                    .zip(faucet_client.get_multiple_accounts(&receipts).await?)
+                    .filter_map(|(metadata, receipt)| receipt.map(|_| metadata.difficulty))
+                    .collect::<HashSet<_>>();
+                let difficulties = candidate_faucets
+                    .iter()
+                    .map(|metadata| metadata.difficulty)
+                    .collect_vec();
 
-                    match client.send_and_confirm_transaction(&transaction).await {
+                while let Some(metadata) = candidate_faucets.pop() {
+                    if claimed_difficulties.contains(&metadata.difficulty) {
+                        continue;
+                    }
+                    match faucet_client
+                        .claim(&payer, &signer, &metadata, mint_info.as_ref())
+                        .await
+                    {
                         Ok(txid) => {
-                            println!(
-                                "Received {} SOL from faucet {}: {}",
-                                reward, metadata.faucet_pubkey, txid
-                            );
+                            let reward = format_ui_amount(metadata.amount, decimals);
+                            if output.is_json() {
+                                output.print_line(&ClaimEvent::Claimed {
+                                    signature: txid.to_string(),
+                                    signer: signer.pubkey().to_string(),
+                                    spec: metadata.spec_pubkey.to_string(),
+                                    faucet: metadata.faucet_pubkey.to_string(),
+                                    difficulty: metadata.difficulty,
+                                    amount: metadata.amount,
+                                    reward: to_ui_amount(metadata.amount, decimals),
+                                })?;
+                            } else {
+                                println!(
+                                    "Received {} {} from faucet {} with {}: {}",
+                                    reward,
+                                    currency,
+                                    metadata.faucet_pubkey,
+                                    signer.pubkey(),
+                                    txid
+                                );
+                            }
                             airdropped_amount += metadata.amount;
-                            matched_difficulties.push(metadata.difficulty);
+                            claimed_difficulties.insert(metadata.difficulty);
+                            if let Some(balance) = balances.get_mut(&metadata.spec_pubkey) {
+                                *balance = balance.saturating_sub(metadata.amount);
+                            }
                         }
                         Err(e) => {
-                            println!("Failed to recieve airdrop: {}", e);
-                            continue;
+                            if output.is_json() {
+                                output.print_line(&ClaimEvent::Failed {
+                                    error: e.to_string(),
+                                    signer: signer.pubkey().to_string(),
+                                    spec: metadata.spec_pubkey.to_string(),
+                                    faucet: metadata.faucet_pubkey.to_string(),
+                                    difficulty: metadata.difficulty,
+                                })?;
+                            }
+                            match decode_faucet_error(&e) {
+                                Some(FaucetError::AlreadyClaimed) => {
+                                    claimed_difficulties.insert(metadata.difficulty);
+                                }
+                                _ => info!(
+                                    output,
+                                    "Failed to claim from faucet {} with {}: {}",
+                                    metadata.faucet_pubkey,
+                                    signer.pubkey(),
+                                    e
+                                ),
+                            }
                         }
                     }
                 }
+
+                // Keys that still have something to claim stay put so the next run retries them
+                if difficulties
+                    .iter()
+                    .all(|difficulty| claimed_difficulties.contains(difficulty))
+                {
+                    std::fs::create_dir_all(&claimed_dir)?;
+                    let file_name = path
+                        .file_name()
+                        .ok_or_else(|| anyhow!("Invalid key path"))?;
+                    std::fs::rename(&path, claimed_dir.join(file_name))?;
+                } else {
+                    info!(
+                        output,
+                        "Keeping {} to retry its unclaimed difficulties",
+                        signer.pubkey()
+                    );
+                }
+            }
+            info!(
+                output,
+                "Claimed {} {}",
+                format_ui_amount(airdropped_amount, decimals),
+                currency
+            );
+            Ok(())
+        }
+        RpcCommand::ReclaimReceipts => {
+            let slot = faucet_client.clock().await?.slot;
+            let receipts = faucet_client.receipts(&payer.pubkey()).await?;
+            let eligible = receipts
+                .iter()
+                .filter(|account| {
+                    matches!(account.receipt.unlock_slot, Some(unlock_slot) if unlock_slot <= slot)
+                })
+                .collect_vec();
+            info!(
+                output,
+                "Found {} receipts, {} of which can be closed",
+                receipts.len(),
+                eligible.len()
+            );
+
+            // Closed receipts keep the rent of an empty account, so their work stays redeemed
+            let empty_rent = client.get_minimum_balance_for_rent_exemption(0).await?;
+            let mut reclaimed = 0;
+            let mut closed = 0;
+            let mut failed = 0;
+            let mut signatures = vec![];
+            for chunk in eligible.chunks(RECEIPTS_PER_TRANSACTION) {
+                let pubkeys = chunk.iter().map(|account| account.pubkey).collect_vec();
+                match faucet_client.close_receipts(&payer, &pubkeys).await {
+                    Ok(txid) => {
+                        let lamports: u64 = chunk
+                            .iter()
+                            .map(|account| account.lamports.saturating_sub(empty_rent))
+                            .sum();
+                        info!(
+                            output,
+                            "Closed {} receipts for {} SOL: {}",
+                            chunk.len(),
+                            format_ui_amount(lamports, 9),
+                            txid
+                        );
+                        reclaimed += lamports;
+                        closed += chunk.len();
+                        signatures.push(txid.to_string());
+                    }
+                    Err(e) => {
+                        info!(output, "Failed to close {} receipts: {}", chunk.len(), e);
+                        failed += chunk.len();
+                    }
+                }
+            }
+            if failed > 0 {
+                info!(output, "Failed to close {} receipts", failed);
+            }
+            info!(
+                output,
+                "Closed {} receipts and reclaimed {} SOL",
+                closed,
+                format_ui_amount(reclaimed, 9)
+            );
+            if output.is_json() {
+                output.print(&ReclaimRecord {
+                    receipts: receipts.len(),
+                    closed,
+                    failed,
+                    reclaimed,
+                    signatures,
+                })?;
             }
             Ok(())
         }
     }
 }
 
-async fn get_all_faucets(
+/// Fetches the most recent entry of the SlotHashes sysvar to grind against.
+async fn get_recent_slot_hash(
     client: &RpcClient,
-    commitment: &CommitmentConfig,
-) -> anyhow::Result<Vec<FaucetMetadata>> {
-    let config = RpcProgramAccountsConfig {
-        filters: Some(vec![RpcFilterType::DataSize(17)]),
-        account_config: RpcAccountInfoConfig {
-            encoding: Some(UiAccountEncoding::Binary),
-            commitment: Some(*commitment),
-            ..RpcAccountInfoConfig::default()
-        },
-        ..RpcProgramAccountsConfig::default()
-    };
-    let specs = client
-        .get_program_accounts_with_config(&proof_of_work_faucet::id(), config)
+    commitment: CommitmentConfig,
+) -> anyhow::Result<(u64, [u8; 32])> {
+    let account = client
+        .get_account_with_commitment(&sysvar::slot_hashes::id(), commitment)
         .await?
-        .iter()
-        .filter_map(|(pubkey, account)| {
-            let difficulty = Difficulty::try_from_slice(&account.data[8..]).ok()?;
-            let (faucet, _) = Pubkey::find_program_address(
-                &[b"source", pubkey.as_ref()],
-                &proof_of_work_faucet::id(),
-            );
-            Some(FaucetMetadata {
-                spec_pubkey: *pubkey,
-                faucet_pubkey: faucet,
-                difficulty: difficulty.difficulty,
-                amount: difficulty.amount,
-            })
-        })
-        .collect_vec();
-    Ok(specs)
+        .value
+        .ok_or_else(|| anyhow!("SlotHashes sysvar not found"))?;
+    let slot_hashes = bincode::deserialize::<SlotHashes>(&account.data)?;
+    let (slot, hash) = slot_hashes
+        .first()
+        .ok_or_else(|| anyhow!("SlotHashes sysvar is empty"))?;
+    Ok((*slot, hash.to_bytes()))
 }
 
-async fn get_inferred_faucets(
-    client: &RpcClient,
-    commitment: &CommitmentConfig,
-    difficulty: Option<u8>,
-    reward: Option<f64>,
-) -> anyhow::Result<BTreeMap<u8, BTreeMap<u64, FaucetMetadata>>> {
-    let mut faucet_specs = get_all_faucets(client, commitment)
-        .await?
-        .iter()
-        .filter(|spec_metadata| {
-            if let Some(difficulty) = difficulty {
-                if spec_metadata.difficulty < difficulty {
-                    return false;
-                }
+/// Number of grinding threads, defaulting to one per core.
+fn resolve_threads(threads: Option<usize>) -> usize {
+    match threads {
+        Some(threads) => threads,
+        None => available_parallelism().map_or(1, |threads| threads.get()),
+    }
+}
+
+/// Grinds `count` signer keypairs with at least `difficulty` leading 'A's and writes each one to
+/// `out_dir` as `<pubkey>.json`.
+async fn grind(
+    difficulty: u8,
+    count: usize,
+    out_dir: &Path,
+    threads: Option<usize>,
+    quiet: bool,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(out_dir)?;
+    let threads = resolve_threads(threads);
+    let mut grinder = Grinder::spawn_keypairs(threads, difficulty);
+    info!(
+        output,
+        "Grinding {} keypairs with difficulty {} on {} threads...", count, difficulty, threads
+    );
+
+    let scorer = PrefixScorer::new();
+    let mut progress = Progress::new("keys", "keypairs".to_string(), 0, count as u64, output);
+    let mut status = tokio::time::interval_at(
+        tokio::time::Instant::now() + STATUS_INTERVAL,
+        STATUS_INTERVAL,
+    );
+    status.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut found = 0;
+    while found < count {
+        let Hit {
+            signer: keypair,
+            prefix_len,
+        } = tokio::select! {
+            hit = grinder.next() => match hit {
+                Some(hit) => hit,
+                None => break,
+            },
+            _ = status.tick(), if !quiet => {
+                progress.report(
+                    grinder.keys_tried(),
+                    found as u64,
+                    std::iter::once(difficulty),
+                    |difficulty| scorer.probability(difficulty as usize),
+                );
+                continue;
             }
-            if let Some(reward) = reward {
-                let reward_as_amount = (reward * 1e9) as u64;
-                if spec_metadata.amount < reward_as_amount {
-                    return false;
+        };
+        let path = out_dir.join(format!("{}.json", keypair.pubkey()));
+        write_keypair_file(&keypair, &path).map_err(|e| anyhow!("{}", e))?;
+        found += 1;
+        if output.is_json() {
+            output.print_line(&GroundKeyRecord {
+                pubkey: keypair.pubkey().to_string(),
+                prefix_len,
+                path: path.display().to_string(),
+            })?;
+        } else {
+            println!(
+                "Keypair mined! Pubkey: {} (prefix length {}): {}",
+                keypair.pubkey(),
+                prefix_len,
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn mine_hash(
+    faucet_client: &FaucetClient,
+    payer: &Keypair,
+    mut faucet_specs: BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>,
+    mut balances: HashMap<Pubkey, u64>,
+    strategy: SelectionStrategy,
+    target_lamports: u64,
+    threads: Option<usize>,
+    quiet: bool,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let client = faucet_client.rpc();
+    let commitment = client.commitment();
+    // This variable is used to short circuit the loop if the hash has fewer leading zero bits than the
+    // strategy targets
+    let mut min_zero_bits = min_difficulty(&strategy, &faucet_specs, &balances)
+        .ok_or_else(|| anyhow!("No faucets found"))?;
+
+    let (slot, slot_hash) = get_recent_slot_hash(client, commitment).await?;
+    let threads = resolve_threads(threads);
+    let mut grinder = HashGrinder::spawn(threads, payer.pubkey(), slot, slot_hash, min_zero_bits);
+
+    info!(output, "Minimum difficulty: {} bits", min_zero_bits);
+    info!(
+        output,
+        "Setup complete! Starting mining process on {} threads...", threads
+    );
+    info!(output, "");
+    let mut airdropped_amount = 0;
+
+    let mut progress = Progress::new("hashes", "SOL".to_string(), 9, target_lamports, output);
+    let mut status = tokio::time::interval_at(
+        tokio::time::Instant::now() + STATUS_INTERVAL,
+        STATUS_INTERVAL,
+    );
+    status.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Work is tied to the slot hash, so switch to a fresh one before the current one expires
+    let mut challenge_refresh = tokio::time::interval_at(
+        tokio::time::Instant::now() + CHALLENGE_REFRESH_INTERVAL,
+        CHALLENGE_REFRESH_INTERVAL,
+    );
+    challenge_refresh.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut claims = ClaimPool::new(client, payer, commitment, MAX_IN_FLIGHT_CLAIMS);
+    claims.set_compute_unit_price(faucet_client.compute_unit_price());
+    let mut confirmations = tokio::time::interval(CONFIRMATION_POLL_INTERVAL);
+    confirmations.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Claims against an older slot hash can fail after the grinder moved on, which needs no refresh
+    let mut challenge_slot = slot;
+
+    while airdropped_amount < target_lamports {
+        grinder.set_min_zero_bits(min_zero_bits);
+        tokio::select! {
+            hit = grinder.next(), if claims.has_capacity() => {
+                let NonceHit {
+                    slot,
+                    nonce,
+                    zero_bits,
+                } = match hit {
+                    Some(hit) => hit,
+                    None => break,
+                };
+
+                // Hits found before the minimum was raised can still be queued
+                if zero_bits < min_zero_bits as u32 {
+                    continue;
                 }
-            }
-            // Ignore specs that are not profitable to mine
-            if spec_metadata.amount < 895880 {
-                return false;
-            }
-            true
-        })
-        .group_by(|spec_metadata| spec_metadata.difficulty)
-        .into_iter()
-        .map(|(key, group)| {
-            let specs_for_difficulty = group
-                .map(|spec| (spec.amount, *spec))
-                .collect::<BTreeMap<u64, FaucetMetadata>>();
-            (key, specs_for_difficulty)
-        })
-        .collect::<BTreeMap<u8, BTreeMap<u64, FaucetMetadata>>>();
 
-    let mut keys_to_remove = vec![];
+                let candidate_faucets =
+                    qualifying_faucets(&faucet_specs, &balances, &strategy, zero_bits as usize)
+                        .into_iter()
+                        .copied()
+                        .collect_vec();
 
-    for (difficulty, specs_for_difficulty) in faucet_specs.iter() {
-        for (amount, spec) in specs_for_difficulty.iter() {
-            // Make sure this is a valid faucet
-            match client.get_account(&spec.spec_pubkey).await {
-                Ok(acc) => {
-                    if acc.data.is_empty() {
-                        keys_to_remove.push((*difficulty, *amount));
+                info!(
+                    output,
+                    "Nonce mined! Slot: {}, nonce: {}, zero bits: {}", slot, nonce, zero_bits
+                );
+
+                // Every faucet the nonce qualifies for is checked in a single request
+                let candidate_balances = faucet_client.balances(&candidate_faucets).await?;
+                let mut pending_claims = vec![];
+                for (metadata, balance) in candidate_faucets.into_iter().zip(candidate_balances) {
+                    balances.insert(metadata.spec_pubkey, balance);
+                    if balance < metadata.amount {
+                        // Remove this key from the global list of faucets
+                        info!(output, "Faucet {} is empty", metadata.faucet_pubkey);
+                        remove_faucet(&mut faucet_specs, &metadata);
+                        continue;
                     }
+                    let ix = hash_airdrop_ix(
+                        faucet_client.program_id(),
+                        &payer.pubkey(),
+                        &metadata,
+                        slot,
+                        nonce,
+                    );
+                    pending_claims.push((ix, HashClaim { slot, nonce, metadata }));
                 }
-                Err(_) => {
-                    keys_to_remove.push((*difficulty, *amount));
+                // Receipts are per difficulty, so the nonce claims from one faucet at each
+                // difficulty. Only the payer signs, so the claims share transactions
+                claims.submit(None, pending_claims).await;
+
+                min_zero_bits = match min_difficulty(&strategy, &faucet_specs, &balances) {
+                    Some(min) => min,
+                    None => {
+                        info!(output, "No faucets remaining");
+                        return Ok(());
+                    }
+                };
+            }
+            _ = confirmations.tick(), if !claims.is_empty() => {
+                let outcomes = match claims.poll().await {
+                    Ok(outcomes) => outcomes,
+                    Err(e) => {
+                        info!(output, "Failed to check claim status: {}", e);
+                        continue;
+                    }
+                };
+                for (HashClaim { slot, nonce, metadata }, outcome) in outcomes {
+                    let e = match outcome {
+                        Ok(txid) => {
+                            if output.is_json() {
+                                output.print_line(&ClaimEvent::Claimed {
+                                    signature: txid.to_string(),
+                                    signer: payer.pubkey().to_string(),
+                                    spec: metadata.spec_pubkey.to_string(),
+                                    faucet: metadata.faucet_pubkey.to_string(),
+                                    difficulty: metadata.difficulty,
+                                    amount: metadata.amount,
+                                    reward: to_ui_amount(metadata.amount, 9),
+                                })?;
+                            } else {
+                                println!(
+                                    "Received {} SOL from faucet {}: {}",
+                                    format_ui_amount(metadata.amount, 9),
+                                    metadata.faucet_pubkey,
+                                    txid
+                                );
+                            }
+                            airdropped_amount += metadata.amount;
+                            // A faucet that is running dry is worth less to the strategy
+                            if let Some(balance) = balances.get_mut(&metadata.spec_pubkey) {
+                                *balance = balance.saturating_sub(metadata.amount);
+                            }
+                            if let Some(min) = min_difficulty(&strategy, &faucet_specs, &balances) {
+                                min_zero_bits = min;
+                            }
+                            continue;
+                        }
+                        Err(e) => e,
+                    };
+                    if output.is_json() {
+                        output.print_line(&ClaimEvent::Failed {
+                            error: e.to_string(),
+                            signer: payer.pubkey().to_string(),
+                            spec: metadata.spec_pubkey.to_string(),
+                            faucet: metadata.faucet_pubkey.to_string(),
+                            difficulty: metadata.difficulty,
+                        })?;
+                    }
+                    match e.transaction_error().as_ref().and_then(faucet_error) {
+                        Some(FaucetError::AlreadyClaimed) => {
+                            info!(
+                                output,
+                                "Nonce {} has already been claimed from faucet {}",
+                                nonce,
+                                metadata.faucet_pubkey
+                            );
+                            continue;
+                        }
+                        Some(FaucetError::SlotHashNotFound) => {
+                            info!(output, "Slot {} is no longer recent", slot);
+                            // The challenge expired before the claim landed, so start on a fresh one
+                            if slot == challenge_slot {
+                                let (slot, slot_hash) =
+                                    get_recent_slot_hash(client, commitment).await?;
+                                grinder.set_challenge(slot, slot_hash);
+                                challenge_slot = slot;
+                                challenge_refresh.reset();
+                            }
+                            continue;
+                        }
+                        Some(FaucetError::InsufficientDifficulty) => {
+                            // The faucet retargeted since we fetched it
+                            info!(
+                                output,
+                                "Faucet {} raised its difficulty", metadata.faucet_pubkey
+                            );
+                            refresh_faucet(faucet_client, &mut faucet_specs, &metadata).await;
+                        }
+                        Some(
+                            error @ (FaucetError::FaucetEmpty
+                            | FaucetError::InsufficientFaucetBalance
+                            | FaucetError::BudgetExceeded),
+                        ) => {
+                            info!(
+                                output,
+                                "Dropping faucet {}: {}", metadata.faucet_pubkey, error
+                            );
+                            remove_faucet(&mut faucet_specs, &metadata);
+                        }
+                        _ => {
+                            info!(output, "Failed to recieve airdrop: {}", e);
+                            continue;
+                        }
+                    };
+                    min_zero_bits = match min_difficulty(&strategy, &faucet_specs, &balances) {
+                        Some(min) => min,
+                        None => {
+                            info!(output, "No faucets remaining");
+                            return Ok(());
+                        }
+                    };
                 }
             }
-            let balaance = client
-                .get_balance_with_commitment(&spec.faucet_pubkey, *commitment)
-                .await?
-                .value;
-            if balaance < *amount {
-                keys_to_remove.push((*difficulty, *amount));
+            _ = challenge_refresh.tick() => {
+                let (slot, slot_hash) = get_recent_slot_hash(client, commitment).await?;
+                grinder.set_challenge(slot, slot_hash);
+                challenge_slot = slot;
+            }
+            _ = status.tick(), if !quiet => {
+                progress.report(
+                    grinder.hashes_tried(),
+                    airdropped_amount,
+                    faucet_specs.keys().copied(),
+                    |difficulty| 0.5f64.powi(difficulty as i32),
+                );
             }
         }
     }
+    Ok(())
+}
 
-    // Clean up all invalid faucets
-    let mut difficulties_to_remove = vec![];
-    for (difficulty, amount) in keys_to_remove {
-        faucet_specs.get_mut(&difficulty).unwrap().remove(&amount);
-        if faucet_specs.get(&difficulty).unwrap().is_empty() {
-            difficulties_to_remove.push(difficulty);
-        }
-    }
-    for difficulty in difficulties_to_remove {
-        faucet_specs.remove(&difficulty);
+/// A nonce's claim from one faucet.
+struct HashClaim {
+    /// Slot whose hash the nonce was solved against.
+    slot: u64,
+    nonce: u64,
+    metadata: FaucetMetadata,
+}
+
+/// A key's claim from one faucet.
+struct PendingClaim {
+    signer: Arc<GroundSigner>,
+    metadata: FaucetMetadata,
+}
+
+/// One claim at every difficulty a key qualifies for, since receipts are per difficulty. The faucets
+/// `strategy` values most come first, so they are packed into the first transaction.
+fn plan_claims(
+    faucet_specs: &BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>,
+    balances: &HashMap<Pubkey, u64>,
+    strategy: &SelectionStrategy,
+    signer: Arc<GroundSigner>,
+    prefix_len: usize,
+) -> Vec<PendingClaim> {
+    qualifying_faucets(faucet_specs, balances, strategy, prefix_len)
+        .into_iter()
+        .map(|metadata| PendingClaim {
+            signer: signer.clone(),
+            metadata: *metadata,
+        })
+        .collect()
+}
+
+/// Sends one key's claims to the pool without waiting for them to confirm.
+async fn submit_claims(
+    claims: &mut ClaimPool<'_, PendingClaim>,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    mint_info: Option<&MintInfo>,
+    pending_claims: Vec<PendingClaim>,
+) {
+    let signer = match pending_claims.first() {
+        Some(claim) => claim.signer.clone(),
+        None => return,
+    };
+    let batch = pending_claims
+        .into_iter()
+        .map(|claim| {
+            let ix = airdrop_ix(
+                program_id,
+                payer,
+                &claim.signer.pubkey(),
+                &claim.metadata,
+                mint_info,
+            );
+            (ix, claim)
+        })
+        .collect();
+    claims.submit(Some(signer), batch).await;
+}
+
+/// Formats a signed lamport amount in SOL.
+fn format_net_value(net_value: i64) -> String {
+    let sol = format_ui_amount(net_value.unsigned_abs(), 9);
+    if net_value < 0 {
+        format!("-{}", sol)
+    } else {
+        sol
     }
+}
 
-    Ok(faucet_specs)
+fn print_stats(stats: &FaucetStats, decimals: u8, currency: &str) {
+    println!("Total claims: {}", stats.total_claims);
+    println!(
+        "Total distributed: {} {}",
+        format_ui_amount(stats.total_distributed, decimals),
+        currency
+    );
+    if stats.total_claims > 0 {
+        println!("Last claim slot: {}", stats.last_claim_slot);
+        println!("Highest prefix length: {}", stats.max_prefix_len);
+    }
 }
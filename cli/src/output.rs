@@ -0,0 +1,262 @@
+use clap::ValueEnum;
+use proof_of_work_faucet::{AdaptiveConfig, FaucetStats, Mode, RemainingBudget};
+use serde::Serialize;
+
+use devnet_pow::{to_ui_amount, FaucetMetadata};
+
+/// How subcommands print their results.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// Pretty-printed JSON. Streams of records are printed as JSON lines
+    Json,
+    /// JSON with one record per line
+    JsonCompact,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        self != OutputFormat::Text
+    }
+
+    /// Prints a single result record.
+    pub fn print<T: Serialize>(self, record: &T) -> anyhow::Result<()> {
+        match self {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(record)?),
+            _ => println!("{}", serde_json::to_string(record)?),
+        }
+        Ok(())
+    }
+
+    /// Prints one record of a stream. Streams are JSON lines in both JSON formats so they can be
+    /// consumed while the command is still running.
+    pub fn print_line<T: Serialize>(self, record: &T) -> anyhow::Result<()> {
+        println!("{}", serde_json::to_string(record)?);
+        Ok(())
+    }
+}
+
+/// Prints a human-readable message. With a JSON output format it goes to stderr instead, so stdout
+/// only carries records.
+macro_rules! info {
+    ($output:expr, $($arg:tt)*) => {
+        if $output.is_json() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+pub(crate) use info;
+
+pub fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Vanity => "vanity",
+        Mode::Hash => "hash",
+    }
+}
+
+#[derive(Serialize)]
+pub struct AdaptiveRecord {
+    pub target_claims_per_hour: u32,
+    pub window: i64,
+    pub min_difficulty: u8,
+    pub max_difficulty: u8,
+}
+
+impl From<AdaptiveConfig> for AdaptiveRecord {
+    fn from(adaptive: AdaptiveConfig) -> Self {
+        Self {
+            target_claims_per_hour: adaptive.target_claims_per_hour,
+            window: adaptive.window,
+            min_difficulty: adaptive.min_difficulty,
+            max_difficulty: adaptive.max_difficulty,
+        }
+    }
+}
+
+/// Throughput left in the current cap window. A `None` field is uncapped.
+#[derive(Serialize)]
+pub struct BudgetRecord {
+    pub amount: Option<u64>,
+    pub claims: Option<u32>,
+}
+
+impl From<RemainingBudget> for BudgetRecord {
+    fn from(budget: RemainingBudget) -> Self {
+        Self {
+            amount: budget.lamports,
+            claims: budget.claims,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StatsRecord {
+    pub total_claims: u64,
+    pub total_distributed: u64,
+    pub last_claim_slot: u64,
+    pub max_prefix_len: u8,
+}
+
+impl From<FaucetStats> for StatsRecord {
+    fn from(stats: FaucetStats) -> Self {
+        Self {
+            total_claims: stats.total_claims,
+            total_distributed: stats.total_distributed,
+            last_claim_slot: stats.last_claim_slot,
+            max_prefix_len: stats.max_prefix_len,
+        }
+    }
+}
+
+/// A faucet and its balance. Amounts are in lamports (or token base units), with `reward` and
+/// `ui_balance` converted to SOL (or tokens).
+#[derive(Serialize)]
+pub struct FaucetRecord {
+    pub spec: String,
+    pub faucet: String,
+    /// Whether the spec account exists. A faucet address can be funded before its spec is created.
+    pub created: bool,
+    pub mode: &'static str,
+    pub mint: Option<String>,
+    pub difficulty: u8,
+    pub effective_difficulty: u8,
+    pub amount: u64,
+    pub reward: f64,
+    pub balance: u64,
+    pub ui_balance: f64,
+    /// Lamports a claim nets after receipt rent and fees. `None` for token faucets.
+    pub net_value: Option<i64>,
+    pub adaptive: Option<AdaptiveRecord>,
+    pub remaining_budget: Option<BudgetRecord>,
+    pub stats: StatsRecord,
+}
+
+impl FaucetRecord {
+    pub fn new(
+        metadata: &FaucetMetadata,
+        created: bool,
+        balance: u64,
+        decimals: u8,
+        net_value: Option<i64>,
+    ) -> Self {
+        Self {
+            spec: metadata.spec_pubkey.to_string(),
+            faucet: metadata.faucet_pubkey.to_string(),
+            created,
+            mode: mode_name(metadata.mode),
+            mint: metadata.mint.map(|mint| mint.to_string()),
+            difficulty: metadata.difficulty,
+            effective_difficulty: metadata.effective_difficulty,
+            amount: metadata.amount,
+            reward: to_ui_amount(metadata.amount, decimals),
+            balance,
+            ui_balance: to_ui_amount(balance, decimals),
+            net_value,
+            adaptive: metadata.adaptive.map(AdaptiveRecord::from),
+            remaining_budget: metadata.remaining_budget.map(BudgetRecord::from),
+            stats: metadata.stats.into(),
+        }
+    }
+}
+
+/// Result of `create`. `signature` is `None` if the faucet already existed.
+#[derive(Serialize)]
+pub struct CreateRecord {
+    pub signature: Option<String>,
+    pub spec: String,
+    pub faucet: String,
+    pub authority: String,
+    pub mode: &'static str,
+    pub mint: Option<String>,
+    pub difficulty: u8,
+    pub amount: u64,
+    pub reward: f64,
+}
+
+/// Result of a subcommand that sends a single transaction to a faucet.
+#[derive(Serialize)]
+pub struct TransactionRecord {
+    pub signature: String,
+    pub spec: String,
+    pub faucet: String,
+}
+
+/// An airdrop claimed, or failed to claim, by `mine` or `claim`. `signer` is the key that did the
+/// work, which is the payer in hash mode.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ClaimEvent {
+    Claimed {
+        signature: String,
+        signer: String,
+        spec: String,
+        faucet: String,
+        difficulty: u8,
+        amount: u64,
+        reward: f64,
+    },
+    Failed {
+        error: String,
+        signer: String,
+        spec: String,
+        faucet: String,
+        difficulty: u8,
+    },
+}
+
+/// A faucet event from `history`.
+#[derive(Serialize)]
+pub struct HistoryRecord {
+    pub slot: u64,
+    pub signature: String,
+    #[serde(flatten)]
+    pub event: HistoryEvent,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    Created {
+        authority: String,
+        difficulty: u8,
+        amount: u64,
+    },
+    Claimed {
+        payer: String,
+        signer: String,
+        amount: u64,
+        prefix_len: u8,
+    },
+    Funded {
+        funder: String,
+        amount: u64,
+    },
+    Withdrawn {
+        authority: String,
+        amount: u64,
+    },
+}
+
+/// Result of `reclaim-receipts`.
+#[derive(Serialize)]
+pub struct ReclaimRecord {
+    pub receipts: usize,
+    /// Receipts whose close transaction confirmed.
+    pub closed: usize,
+    /// Receipts that could be closed but whose close transaction failed.
+    pub failed: usize,
+    pub reclaimed: u64,
+    pub signatures: Vec<String>,
+}
+
+/// A keypair saved by `grind`.
+#[derive(Serialize)]
+pub struct GroundKeyRecord {
+    pub pubkey: String,
+    pub prefix_len: usize,
+    pub path: String,
+}
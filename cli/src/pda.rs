@@ -0,0 +1,75 @@
+//! Addresses of the faucet program's accounts, for the program deployed at `program_id`.
+
+use proof_of_work_faucet::Mode;
+use solana_sdk::pubkey::Pubkey;
+
+/// Token faucet specs and receipts carry the mint as an extra seed.
+fn mint_seed(mint: &Option<Pubkey>) -> &[u8] {
+    mint.as_ref().map_or(&[], |mint| mint.as_ref())
+}
+
+/// Spec account of the faucet with this difficulty, reward amount, mode and mint.
+pub fn spec(
+    difficulty: u8,
+    amount: u64,
+    mode: Mode,
+    mint: Option<Pubkey>,
+    program_id: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"spec",
+            difficulty.to_le_bytes().as_ref(),
+            amount.to_le_bytes().as_ref(),
+            mode.seed(),
+            mint_seed(&mint),
+        ],
+        program_id,
+    )
+    .0
+}
+
+/// Account holding the SOL (or tokens) a faucet pays out.
+pub fn source(spec: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"source", spec.as_ref()], program_id).0
+}
+
+/// Receipt a vanity or token airdrop with `signer` creates at `difficulty`.
+pub fn vanity_receipt(
+    signer: &Pubkey,
+    difficulty: u8,
+    mint: Option<Pubkey>,
+    program_id: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"receipt",
+            signer.as_ref(),
+            difficulty.to_le_bytes().as_ref(),
+            mint_seed(&mint),
+        ],
+        program_id,
+    )
+    .0
+}
+
+/// Receipt a hash airdrop creates for a solved challenge.
+pub fn hash_receipt(
+    payer: &Pubkey,
+    slot: u64,
+    nonce: u64,
+    difficulty: u8,
+    program_id: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"receipt",
+            payer.as_ref(),
+            slot.to_le_bytes().as_ref(),
+            nonce.to_le_bytes().as_ref(),
+            difficulty.to_le_bytes().as_ref(),
+        ],
+        program_id,
+    )
+    .0
+}
@@ -0,0 +1,209 @@
+//! Counts leading base58 'A's without base58 encoding the pubkey.
+//!
+//! A 32-byte pubkey with a non-zero first byte encodes to 43 or 44 base58 digits with no leading
+//! '1's. Its base58 string of length `L` starts with `k` 'A's (digit 9) exactly when the pubkey,
+//! read as a big-endian integer, lies in `[lo, lo + 58^(L-k))` where
+//! `lo = 9 * (58^(L-1) + ... + 58^(L-k))`.
+//! Pubkeys with a zero first byte encode with a leading '1' and have no 'A' prefix at all.
+
+/// Base58 digit of 'A'.
+const A_DIGIT: u64 = 9;
+
+/// Lengths of the base58 encoding of a pubkey with a non-zero first byte.
+const ENCODED_LENS: [u32; 2] = [43, 44];
+
+/// Unsigned integer wide enough for every bound, as little-endian 64-bit limbs.
+type Wide = [u64; 5];
+
+fn mul_small(a: &Wide, m: u64) -> Wide {
+    let mut out = [0; 5];
+    let mut carry = 0u128;
+    for (out, limb) in out.iter_mut().zip(a) {
+        let product = *limb as u128 * m as u128 + carry;
+        *out = product as u64;
+        carry = product >> 64;
+    }
+    out
+}
+
+fn add(a: &Wide, b: &Wide) -> Wide {
+    let mut out = [0; 5];
+    let mut carry = 0u128;
+    for i in 0..5 {
+        let sum = a[i] as u128 + b[i] as u128 + carry;
+        out[i] = sum as u64;
+        carry = sum >> 64;
+    }
+    out
+}
+
+fn pow58(exponent: u32) -> Wide {
+    (0..exponent).fold([1, 0, 0, 0, 0], |acc, _| mul_small(&acc, 58))
+}
+
+/// Big-endian bytes of `a`, or `None` if it does not fit in 256 bits.
+fn to_be_bytes(a: &Wide) -> Option<[u8; 32]> {
+    if a[4] != 0 {
+        return None;
+    }
+    let mut bytes = [0; 32];
+    for (chunk, limb) in bytes.chunks_exact_mut(8).zip(a[..4].iter().rev()) {
+        chunk.copy_from_slice(&limb.to_be_bytes());
+    }
+    Some(bytes)
+}
+
+/// Half-open range of pubkeys. An upper bound of `None` means the range runs to the largest pubkey.
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+    lo: [u8; 32],
+    hi: Option<[u8; 32]>,
+}
+
+impl Bounds {
+    fn contains(&self, pubkey: &[u8; 32]) -> bool {
+        &self.lo <= pubkey && !matches!(self.hi, Some(hi) if pubkey >= &hi)
+    }
+}
+
+/// Precomputed bounds for every prefix length.
+pub struct PrefixScorer {
+    /// `bounds[k - 1]` holds the pubkeys with at least `k` leading 'A's, one range per encoded length.
+    bounds: Vec<Vec<Bounds>>,
+}
+
+impl Default for PrefixScorer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrefixScorer {
+    pub fn new() -> Self {
+        let max_len = *ENCODED_LENS.iter().max().unwrap();
+        let bounds = (1..=max_len)
+            .map(|k| {
+                ENCODED_LENS
+                    .iter()
+                    .filter(|&&len| k <= len)
+                    .filter_map(|&len| {
+                        let lo = (1..=k).fold([0; 5], |lo, i| {
+                            add(&lo, &mul_small(&pow58(len - i), A_DIGIT))
+                        });
+                        let hi = add(&lo, &pow58(len - k));
+                        // Ranges that start beyond the largest pubkey are empty
+                        Some(Bounds {
+                            lo: to_be_bytes(&lo)?,
+                            hi: to_be_bytes(&hi),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .take_while(|bounds| !bounds.is_empty())
+            .collect();
+        Self { bounds }
+    }
+
+    /// Chance that a uniformly random pubkey has at least `prefix_len` leading 'A's.
+    pub fn probability(&self, prefix_len: usize) -> f64 {
+        if prefix_len == 0 {
+            return 1.0;
+        }
+        let as_f64 = |bytes: &[u8; 32]| {
+            bytes
+                .iter()
+                .fold(0.0, |acc, byte| acc * 256.0 + *byte as f64)
+        };
+        let key_space = 2f64.powi(256);
+        self.bounds.get(prefix_len - 1).map_or(0.0, |bounds| {
+            bounds
+                .iter()
+                .map(|bounds| bounds.hi.as_ref().map_or(key_space, as_f64) - as_f64(&bounds.lo))
+                .sum::<f64>()
+                / key_space
+        })
+    }
+
+    /// Number of leading 'A's in the base58 encoding of `pubkey`.
+    pub fn prefix_len(&self, pubkey: &[u8; 32]) -> usize {
+        // A string with k + 1 leading 'A's also has k, so the ranges nest and the scan can stop at
+        // the first miss.
+        self.bounds
+            .iter()
+            .take_while(|bounds| bounds.iter().any(|bounds| bounds.contains(pubkey)))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey::Pubkey;
+
+    fn expected(bytes: [u8; 32]) -> usize {
+        proof_of_work_faucet::prefix_len(&Pubkey::new_from_array(bytes))
+    }
+
+    /// The big-endian integers one below and one above `bytes`, wrapping at the ends.
+    fn neighbours(bytes: [u8; 32]) -> [[u8; 32]; 3] {
+        let mut below = bytes;
+        for byte in below.iter_mut().rev() {
+            let (value, borrow) = byte.overflowing_sub(1);
+            *byte = value;
+            if !borrow {
+                break;
+            }
+        }
+        let mut above = bytes;
+        for byte in above.iter_mut().rev() {
+            let (value, carry) = byte.overflowing_add(1);
+            *byte = value;
+            if !carry {
+                break;
+            }
+        }
+        [below, bytes, above]
+    }
+
+    #[test]
+    fn matches_program_at_every_bound() {
+        let scorer = PrefixScorer::new();
+        for bounds in scorer.bounds.iter().flatten() {
+            for edge in [Some(bounds.lo), bounds.hi].into_iter().flatten() {
+                for bytes in neighbours(edge) {
+                    assert_eq!(scorer.prefix_len(&bytes), expected(bytes), "{:?}", bytes);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn matches_program_for_every_leading_two_bytes() {
+        let scorer = PrefixScorer::new();
+        for tail in [0x00, 0x5a, 0xff] {
+            for head in 0..=u16::MAX {
+                let mut bytes = [tail; 32];
+                bytes[..2].copy_from_slice(&head.to_be_bytes());
+                assert_eq!(scorer.prefix_len(&bytes), expected(bytes), "{:?}", bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_program_for_random_keys_with_long_prefixes() {
+        let scorer = PrefixScorer::new();
+        // Random keys rarely start with 'A', so also sample keys just above each lower bound
+        for bounds in scorer.bounds.iter().flatten() {
+            for _ in 0..64 {
+                let mut bytes = bounds.lo;
+                let random: [u8; 16] = rand::random();
+                bytes[16..].copy_from_slice(&random);
+                assert_eq!(scorer.prefix_len(&bytes), expected(bytes), "{:?}", bytes);
+            }
+        }
+        for _ in 0..100_000 {
+            let bytes: [u8; 32] = rand::random();
+            assert_eq!(scorer.prefix_len(&bytes), expected(bytes), "{:?}", bytes);
+        }
+    }
+}
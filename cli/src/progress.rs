@@ -0,0 +1,100 @@
+use std::time::Instant;
+
+use crate::output::{info, OutputFormat};
+
+/// Periodic status line for `mine`: hashrate, expected time to the next hit and amount mined.
+pub struct Progress {
+    start: Instant,
+    last_report: Instant,
+    last_tried: u64,
+    /// What a single attempt is called, e.g. "keys" or "hashes".
+    unit: &'static str,
+    currency: String,
+    decimals: u8,
+    target: u64,
+    output: OutputFormat,
+}
+
+impl Progress {
+    pub fn new(
+        unit: &'static str,
+        currency: String,
+        decimals: u8,
+        target: u64,
+        output: OutputFormat,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            last_report: now,
+            last_tried: 0,
+            unit,
+            currency,
+            decimals,
+            target,
+            output,
+        }
+    }
+
+    /// Prints a status line. `tried` is the running total of attempts and `probability` gives the
+    /// chance that a single attempt meets a difficulty.
+    pub fn report(
+        &mut self,
+        tried: u64,
+        mined: u64,
+        difficulties: impl Iterator<Item = u8>,
+        probability: impl Fn(u8) -> f64,
+    ) {
+        let now = Instant::now();
+        let rate = tried.saturating_sub(self.last_tried) as f64
+            / now
+                .duration_since(self.last_report)
+                .as_secs_f64()
+                .max(f64::EPSILON);
+        self.last_report = now;
+        self.last_tried = tried;
+
+        let etas = difficulties
+            .map(|difficulty| {
+                let expected = 1.0 / (probability(difficulty) * rate);
+                format!("d{} ~{}", difficulty, format_duration(expected))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let scale = 10f64.powi(self.decimals as i32);
+        info!(
+            self.output,
+            "[{}] {} {}/s | mined {}/{} {} | next hit: {}",
+            format_duration(now.duration_since(self.start).as_secs_f64()),
+            format_rate(rate),
+            self.unit,
+            mined as f64 / scale,
+            self.target as f64 / scale,
+            self.currency,
+            etas
+        );
+    }
+}
+
+fn format_rate(rate: f64) -> String {
+    if rate >= 1e6 {
+        format!("{:.2}M", rate / 1e6)
+    } else if rate >= 1e3 {
+        format!("{:.1}k", rate / 1e3)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
+
+fn format_duration(secs: f64) -> String {
+    if !secs.is_finite() {
+        return "never".to_string();
+    }
+    let secs = secs.round() as u64;
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{:02}s", m, s),
+        (h, m, _) if h < 48 => format!("{}h{:02}m", h, m),
+        (h, _, _) => format!("{}d{:02}h", h / 24, h % 24),
+    }
+}
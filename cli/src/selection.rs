@@ -0,0 +1,224 @@
+//! Finding the faucets worth mining, and choosing which of them a key (or nonce) claims from.
+//!
+//! Faucets are kept grouped by effective difficulty, so the lowest difficulty a hit has to meet is
+//! always the first group.
+
+use std::collections::{BTreeMap, HashMap};
+
+use itertools::Itertools;
+use proof_of_work_faucet::{Mode, RemainingBudget};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{FaucetClient, FaucetMetadata, Strategy};
+
+/// Faucets in `mode` paying out `mint` (SOL if `None`) that can pay a full reward right now,
+/// grouped by effective difficulty, along with the balance of each. Faucets below `difficulty` or
+/// `min_amount`, and SOL faucets netting less than `min_net_lamports` after costs, are left out.
+pub async fn get_inferred_faucets(
+    faucet_client: &FaucetClient,
+    mode: Mode,
+    mint: Option<Pubkey>,
+    difficulty: Option<u8>,
+    min_amount: Option<u64>,
+    min_net_lamports: u64,
+) -> anyhow::Result<(
+    BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>,
+    HashMap<Pubkey, u64>,
+)> {
+    let cost = faucet_client.claim_cost(mode).await?;
+    let candidates = faucet_client
+        .list()
+        .await?
+        .into_iter()
+        .filter(|spec_metadata| {
+            if spec_metadata.mode != mode || spec_metadata.mint != mint {
+                return false;
+            }
+            if let Some(difficulty) = difficulty {
+                if spec_metadata.effective_difficulty < difficulty {
+                    return false;
+                }
+            }
+            if let Some(min_amount) = min_amount {
+                if spec_metadata.amount < min_amount {
+                    return false;
+                }
+            }
+            // Ignore specs that have exhausted their throughput cap for now
+            if let Some(RemainingBudget { lamports, claims }) = spec_metadata.remaining_budget {
+                if matches!(lamports, Some(lamports) if lamports < spec_metadata.amount)
+                    || claims == Some(0)
+                {
+                    return false;
+                }
+            }
+            // Ignore SOL specs that net less than `min_net_lamports` after rent and fees
+            cost.clears(spec_metadata, min_net_lamports)
+        })
+        .collect_vec();
+
+    // Ignore faucets that can no longer pay out a full reward
+    let balances = faucet_client.balances(&candidates).await?;
+    let (candidates, balances): (Vec<_>, HashMap<_, _>) = candidates
+        .into_iter()
+        .zip(balances)
+        .filter(|(spec_metadata, balance)| *balance >= spec_metadata.amount)
+        .map(|(spec_metadata, balance)| (spec_metadata, (spec_metadata.spec_pubkey, balance)))
+        .unzip();
+    let faucet_specs = candidates
+        .into_iter()
+        .sorted_by_key(|spec_metadata| spec_metadata.effective_difficulty)
+        .group_by(|spec_metadata| spec_metadata.effective_difficulty)
+        .into_iter()
+        .map(|(key, group)| {
+            let specs_for_difficulty = group
+                .map(|spec| (spec.spec_pubkey, spec))
+                .collect::<BTreeMap<Pubkey, FaucetMetadata>>();
+            (key, specs_for_difficulty)
+        })
+        .collect::<BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>>();
+
+    Ok((faucet_specs, balances))
+}
+
+/// Lowest difficulty `strategy` wants mined among the remaining faucets, or `None` if no faucets
+/// remain.
+pub fn min_difficulty(
+    strategy: &impl Strategy,
+    faucet_specs: &BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>,
+    balances: &HashMap<Pubkey, u64>,
+) -> Option<u8> {
+    strategy.min_difficulty(with_balances(faucet_specs, balances))
+}
+
+/// Every faucet being mined, with the balance it was last known to hold.
+pub fn with_balances<'a>(
+    faucet_specs: &'a BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>,
+    balances: &'a HashMap<Pubkey, u64>,
+) -> impl Iterator<Item = (&'a FaucetMetadata, u64)> {
+    faucet_specs
+        .values()
+        .flat_map(|specs_for_difficulty| specs_for_difficulty.values())
+        .map(|metadata| {
+            (
+                metadata,
+                balances.get(&metadata.spec_pubkey).copied().unwrap_or(0),
+            )
+        })
+}
+
+/// The faucets a hit of `difficulty` claims from, the ones `strategy` values most first. A hit's
+/// receipt is keyed by the faucet's difficulty, so only the most valuable faucet at each difficulty
+/// is included.
+pub fn qualifying_faucets<'a>(
+    faucet_specs: &'a BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>,
+    balances: &'a HashMap<Pubkey, u64>,
+    strategy: &impl Strategy,
+    difficulty: usize,
+) -> Vec<&'a FaucetMetadata> {
+    with_balances(faucet_specs, balances)
+        .filter(|(spec, _)| spec.effective_difficulty as usize <= difficulty)
+        .sorted_by(|(spec1, balance1), (spec2, balance2)| {
+            strategy
+                .value(spec2, *balance2)
+                .total_cmp(&strategy.value(spec1, *balance1))
+        })
+        .unique_by(|(spec, _)| spec.difficulty)
+        .map(|(spec, _)| spec)
+        .collect()
+}
+
+/// Drops a faucet from the set being mined.
+pub fn remove_faucet(
+    faucet_specs: &mut BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>,
+    metadata: &FaucetMetadata,
+) {
+    if let Some(specs_for_difficulty) = faucet_specs.get_mut(&metadata.effective_difficulty) {
+        specs_for_difficulty.remove(&metadata.spec_pubkey);
+        if specs_for_difficulty.is_empty() {
+            faucet_specs.remove(&metadata.effective_difficulty);
+        }
+    }
+}
+
+/// Re-reads a faucet's spec and files it under its current difficulty. The faucet is dropped if
+/// its spec can no longer be read.
+pub async fn refresh_faucet(
+    faucet_client: &FaucetClient,
+    faucet_specs: &mut BTreeMap<u8, BTreeMap<Pubkey, FaucetMetadata>>,
+    metadata: &FaucetMetadata,
+) {
+    remove_faucet(faucet_specs, metadata);
+    let refreshed = match faucet_client.get(&metadata.spec_pubkey).await {
+        Ok(Some(refreshed)) => refreshed,
+        _ => return,
+    };
+    faucet_specs
+        .entry(refreshed.effective_difficulty)
+        .or_default()
+        .insert(refreshed.spec_pubkey, refreshed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_faucet, SelectionStrategy};
+
+    #[test]
+    fn qualifies_the_best_faucet_at_each_difficulty() {
+        let easy = test_faucet(2, 100_000_000);
+        let jackpot = test_faucet(3, 10_000_000_000);
+        let modest = test_faucet(3, 1_000_000_000);
+        let hard = test_faucet(4, 1_000_000_000);
+        let mut faucet_specs = BTreeMap::<u8, BTreeMap<Pubkey, FaucetMetadata>>::new();
+        for metadata in [easy, jackpot, modest, hard] {
+            faucet_specs
+                .entry(metadata.effective_difficulty)
+                .or_default()
+                .insert(metadata.spec_pubkey, metadata);
+        }
+        let balances = [easy, jackpot, modest, hard]
+            .iter()
+            .map(|metadata| (metadata.spec_pubkey, 100_000_000_000))
+            .collect::<HashMap<_, _>>();
+        let qualifying = |faucet_specs: &BTreeMap<_, _>, difficulty| {
+            qualifying_faucets(
+                faucet_specs,
+                &balances,
+                &SelectionStrategy::ExpectedValue,
+                difficulty,
+            )
+            .into_iter()
+            .map(|metadata| metadata.spec_pubkey)
+            .collect_vec()
+        };
+
+        // The jackpot is worth grinding past the easy faucet for, unless ranking by amount alone
+        let strategy = SelectionStrategy::ExpectedValue;
+        assert_eq!(min_difficulty(&strategy, &faucet_specs, &balances), Some(3));
+        assert_eq!(
+            min_difficulty(&SelectionStrategy::Amount, &faucet_specs, &balances),
+            Some(2)
+        );
+        // A hit at a lower difficulty still claims whatever it qualifies for
+        assert_eq!(qualifying(&faucet_specs, 1), vec![]);
+        assert_eq!(qualifying(&faucet_specs, 2), vec![easy.spec_pubkey]);
+        assert_eq!(
+            qualifying(&faucet_specs, 3),
+            vec![jackpot.spec_pubkey, easy.spec_pubkey]
+        );
+        assert_eq!(
+            qualifying(&faucet_specs, 4),
+            vec![jackpot.spec_pubkey, easy.spec_pubkey, hard.spec_pubkey]
+        );
+
+        remove_faucet(&mut faucet_specs, &easy);
+        assert_eq!(min_difficulty(&strategy, &faucet_specs, &balances), Some(3));
+        // Receipts are per difficulty, so the next best faucet at 3 is only claimed without jackpot
+        remove_faucet(&mut faucet_specs, &jackpot);
+        assert_eq!(qualifying(&faucet_specs, 3), vec![modest.spec_pubkey]);
+        remove_faucet(&mut faucet_specs, &modest);
+        remove_faucet(&mut faucet_specs, &hard);
+        assert_eq!(min_difficulty(&strategy, &faucet_specs, &balances), None);
+    }
+}
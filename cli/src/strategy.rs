@@ -0,0 +1,146 @@
+//! How to choose between faucets when a key (or nonce) qualifies for several of them.
+
+use clap::ValueEnum;
+use proof_of_work_faucet::{Mode, RemainingBudget};
+
+use crate::FaucetMetadata;
+
+/// Ranks faucets for mining. Claims go to the most valuable faucet a key qualifies for first, and
+/// the grinder skips keys below [`Strategy::min_difficulty`].
+pub trait Strategy {
+    /// How much mining `metadata` is worth, given the `balance` its source account holds. Only the
+    /// order of values matters.
+    fn value(&self, metadata: &FaucetMetadata, balance: u64) -> f64;
+
+    /// Lowest difficulty worth grinding for among `faucets`: by default, the effective difficulty
+    /// of the most valuable one. `None` if there are no faucets.
+    fn min_difficulty<'a>(
+        &self,
+        faucets: impl IntoIterator<Item = (&'a FaucetMetadata, u64)>,
+    ) -> Option<u8>
+    where
+        Self: Sized,
+    {
+        most_valuable(self, faucets).map(|metadata| metadata.effective_difficulty)
+    }
+}
+
+/// The faucet `strategy` values most.
+pub fn most_valuable<'a>(
+    strategy: &impl Strategy,
+    faucets: impl IntoIterator<Item = (&'a FaucetMetadata, u64)>,
+) -> Option<&'a FaucetMetadata> {
+    faucets
+        .into_iter()
+        .map(|(metadata, balance)| (strategy.value(metadata, balance), metadata))
+        .max_by(|(value1, _), (value2, _)| value1.total_cmp(value2))
+        .map(|(_, metadata)| metadata)
+}
+
+/// The strategies the CLI offers.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// Expected reward per key (or hash) tried, discounted for faucets close to running dry. Keys
+    /// below the difficulty of the best paying faucet are skipped
+    #[default]
+    ExpectedValue,
+    /// Largest reward first. Every key that meets the lowest difficulty is claimed
+    Amount,
+}
+
+impl Strategy for SelectionStrategy {
+    fn value(&self, metadata: &FaucetMetadata, balance: u64) -> f64 {
+        match self {
+            Self::ExpectedValue => expected_value(metadata, balance),
+            Self::Amount => metadata.amount as f64,
+        }
+    }
+
+    fn min_difficulty<'a>(
+        &self,
+        faucets: impl IntoIterator<Item = (&'a FaucetMetadata, u64)>,
+    ) -> Option<u8> {
+        match self {
+            Self::ExpectedValue => {
+                most_valuable(self, faucets).map(|metadata| metadata.effective_difficulty)
+            }
+            Self::Amount => faucets
+                .into_iter()
+                .map(|(metadata, _)| metadata.effective_difficulty)
+                .min(),
+        }
+    }
+}
+
+/// Reward per attempt: the reward times the chance that a random key has `effective_difficulty`
+/// leading 'A's (about 58^-difficulty), or that a random hash has that many leading zero bits.
+///
+/// A faucet that can pay `n` more rewards is discounted by `n / (n + 1)`, since the fewer it has
+/// left, the likelier other miners empty it before a claim lands. Throughput caps count too.
+pub fn expected_value(metadata: &FaucetMetadata, balance: u64) -> f64 {
+    let base: f64 = match metadata.mode {
+        Mode::Vanity => 58.0,
+        Mode::Hash => 2.0,
+    };
+    let probability = base.powi(-(metadata.effective_difficulty as i32));
+
+    let mut payable = balance.checked_div(metadata.amount).unwrap_or(u64::MAX);
+    if let Some(RemainingBudget { lamports, claims }) = metadata.remaining_budget {
+        if let Some(lamports) = lamports {
+            payable = payable.min(lamports.checked_div(metadata.amount).unwrap_or(u64::MAX));
+        }
+        if let Some(claims) = claims {
+            payable = payable.min(claims as u64);
+        }
+    }
+    let payable = payable as f64;
+    metadata.amount as f64 * probability * payable / (payable + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_faucet;
+
+    #[test]
+    fn ranks_by_reward_per_key() {
+        let easy = test_faucet(2, 100_000_000);
+        let hard = test_faucet(3, 1_000_000_000);
+        let jackpot = test_faucet(3, 10_000_000_000);
+        let funded = 100_000_000_000;
+
+        // Ten times the reward does not make up for 58 times the work
+        assert!(expected_value(&easy, funded) > expected_value(&hard, funded));
+        assert!(expected_value(&jackpot, funded) > expected_value(&easy, funded));
+        // A faucet with one reward left is worth less than a well funded one
+        assert!(expected_value(&easy, easy.amount) < expected_value(&easy, funded));
+
+        let best = |strategy, faucets: &[&FaucetMetadata]| {
+            most_valuable(&strategy, faucets.iter().map(|faucet| (*faucet, funded)))
+                .map(|faucet| faucet.amount)
+        };
+        let strategy = SelectionStrategy::ExpectedValue;
+        assert_eq!(best(strategy, &[&easy, &hard]), Some(easy.amount));
+        assert_eq!(best(strategy, &[&easy, &jackpot]), Some(jackpot.amount));
+        assert_eq!(
+            best(SelectionStrategy::Amount, &[&easy, &hard]),
+            Some(hard.amount)
+        );
+        assert_eq!(best(strategy, &[]), None);
+
+        // The grinder targets the best paying difficulty, which isn't always the lowest
+        assert_eq!(
+            strategy.min_difficulty([(&easy, funded), (&hard, funded)]),
+            Some(2)
+        );
+        assert_eq!(
+            strategy.min_difficulty([(&easy, funded), (&jackpot, funded)]),
+            Some(3)
+        );
+        assert_eq!(
+            SelectionStrategy::Amount.min_difficulty([(&easy, funded), (&jackpot, funded)]),
+            Some(2)
+        );
+        assert_eq!(strategy.min_difficulty(std::iter::empty()), None);
+    }
+}
@@ -1,8 +1,16 @@
+#![allow(clippy::result_large_err)]
+
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
     entrypoint::ProgramResult,
+    hash::hashv,
     program::{invoke, invoke_signed},
-    system_instruction,
+    slot_hashes, system_instruction, sysvar,
+};
+use anchor_lang::Discriminator;
+use anchor_spl::{
+    associated_token::{self, AssociatedToken, Create as CreateAssociatedToken},
+    token_interface::{self, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked},
 };
 use bs58::encode;
 
@@ -70,13 +78,296 @@ pub fn create_account<'a, 'info>(
     }
 }
 
+/// Writes the details of a claim into a freshly created receipt account, unless the receipt was
+/// created without space for them.
+pub fn write_receipt(receipt: &AccountInfo, receipt_data: &Receipt) -> Result<()> {
+    let mut data = receipt.try_borrow_mut_data()?;
+    if data.is_empty() {
+        return Ok(());
+    }
+    receipt_data.try_serialize(&mut &mut data[..])
+}
+
+/// Counts the number of leading base58 'A's in a pubkey.
+pub fn prefix_len(pubkey: &Pubkey) -> usize {
+    encode(pubkey.as_ref())
+        .into_string()
+        .chars()
+        .take_while(|ch| ch == &'A')
+        .count()
+}
+
+/// Computes the hash that a `Hash` mode claim must grind: sha256(slot hash || payer || nonce).
+pub fn challenge_hash(slot_hash: &[u8; 32], payer: &Pubkey, nonce: u64) -> [u8; 32] {
+    hashv(&[slot_hash, payer.as_ref(), &nonce.to_le_bytes()]).to_bytes()
+}
+
+/// Counts the number of leading zero bits in a hash.
+pub fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Looks up the hash of `slot` in the raw SlotHashes sysvar data.
+///
+/// The sysvar holds up to 512 entries, so it is scanned in place rather than deserialized.
+pub fn find_slot_hash(slot_hashes_data: &[u8], slot: u64) -> Option<[u8; 32]> {
+    let len = u64::from_le_bytes(slot_hashes_data.get(..8)?.try_into().ok()?) as usize;
+    slot_hashes_data
+        .get(8..)?
+        .chunks_exact(40)
+        .take(len)
+        .find(|entry| entry[..8] == slot.to_le_bytes())
+        .and_then(|entry| entry[8..].try_into().ok())
+}
+
 #[program]
 pub mod proof_of_work_faucet {
     use super::*;
 
-    pub fn create(ctx: Context<Create>, difficulty: u8, amount: u64) -> Result<()> {
-        ctx.accounts.spec.difficulty = difficulty;
-        ctx.accounts.spec.amount = amount;
+    pub fn create(
+        ctx: Context<Create>,
+        difficulty: u8,
+        amount: u64,
+        config: FaucetConfig,
+    ) -> Result<()> {
+        let authority = ctx.accounts.payer.key();
+        ctx.accounts
+            .spec
+            .init(difficulty, amount, authority, config, None)?;
+        emit!(FaucetCreated {
+            spec: ctx.accounts.spec.key(),
+            authority,
+            difficulty,
+            amount,
+            mode: config.mode,
+            mint: None,
+        });
+        Ok(())
+    }
+
+    pub fn create_token(
+        ctx: Context<CreateToken>,
+        difficulty: u8,
+        amount: u64,
+        config: FaucetConfig,
+    ) -> Result<()> {
+        // Token claims are made by `airdrop_token`, which only checks vanity pubkeys
+        require!(config.mode == Mode::Vanity, FaucetError::InvalidConfig);
+        let authority = ctx.accounts.payer.key();
+        let mint = ctx.accounts.mint.key();
+        ctx.accounts
+            .spec
+            .init(difficulty, amount, authority, config, Some(mint))?;
+        emit!(FaucetCreated {
+            spec: ctx.accounts.spec.key(),
+            authority,
+            difficulty,
+            amount,
+            mode: config.mode,
+            mint: Some(mint),
+        });
+        Ok(())
+    }
+
+    /// Upgrades a spec created before specs recorded an authority and config. Migrated faucets
+    /// use the default config and have no authority, since none was ever recorded, so their source
+    /// is still only paid out through claims.
+    pub fn migrate_spec(ctx: Context<MigrateSpec>, difficulty: u8, amount: u64) -> Result<()> {
+        let MigrateSpec {
+            payer,
+            spec,
+            system_program,
+        } = ctx.accounts;
+
+        {
+            let data = spec.try_borrow_data()?;
+            require!(
+                data.len() == 8 + Difficulty::LEGACY_LEN && data[..8] == Difficulty::DISCRIMINATOR,
+                FaucetError::SpecUpToDate
+            );
+        }
+        msg!("Migrating faucet spec {}", spec.key());
+
+        let space = 8 + Difficulty::LEN;
+        let required_lamports = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(spec.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(&payer.key(), &spec.key(), required_lamports),
+                &[
+                    system_program.to_account_info(),
+                    payer.to_account_info(),
+                    spec.to_account_info(),
+                ],
+            )?;
+        }
+        spec.realloc(space, true)?;
+
+        let migrated = Difficulty {
+            difficulty,
+            amount,
+            authority: Pubkey::default(),
+            config: FaucetConfig::default(),
+            current_difficulty: difficulty,
+            window_start: Clock::get()?.unix_timestamp,
+            window_claims: 0,
+            budget_window: 0,
+            budget_lamports_used: 0,
+            budget_claims_used: 0,
+            mint: None,
+            stats: FaucetStats::default(),
+        };
+        let mut data = spec.try_borrow_mut_data()?;
+        migrated.try_serialize(&mut &mut data[..])
+    }
+
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        let Withdraw {
+            authority,
+            spec,
+            source,
+            system_program,
+        } = ctx.accounts;
+
+        let amount = amount.min(source.lamports());
+        msg!("Withdrawing {} lamports to {}", amount, authority.key());
+
+        invoke_signed(
+            &system_instruction::transfer(&source.key(), &authority.key(), amount),
+            &[
+                system_program.to_account_info(),
+                authority.to_account_info(),
+                source.to_account_info(),
+            ],
+            &[&[b"source", spec.key().as_ref(), &[ctx.bumps["source"]]]],
+        )?;
+        emit!(FaucetWithdrawn {
+            spec: spec.key(),
+            authority: authority.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    pub fn close_faucet(ctx: Context<CloseFaucet>) -> Result<()> {
+        let CloseFaucet {
+            authority,
+            spec,
+            source,
+            system_program,
+        } = ctx.accounts;
+
+        // Drain the source wallet so no lamports are stranded once the spec is closed.
+        let remaining = source.lamports();
+        msg!(
+            "Closing faucet and returning {} lamports to {}",
+            remaining,
+            authority.key()
+        );
+
+        if remaining > 0 {
+            invoke_signed(
+                &system_instruction::transfer(&source.key(), &authority.key(), remaining),
+                &[
+                    system_program.to_account_info(),
+                    authority.to_account_info(),
+                    source.to_account_info(),
+                ],
+                &[&[b"source", spec.key().as_ref(), &[ctx.bumps["source"]]]],
+            )?;
+        }
+        emit!(FaucetWithdrawn {
+            spec: spec.key(),
+            authority: authority.key(),
+            amount: remaining,
+        });
+        Ok(())
+    }
+
+    pub fn fund(ctx: Context<Fund>, amount: u64) -> Result<()> {
+        let Fund {
+            funder,
+            spec,
+            source,
+            system_program,
+        } = ctx.accounts;
+
+        msg!("Funding faucet with {} lamports", amount);
+        invoke(
+            &system_instruction::transfer(&funder.key(), &source.key(), amount),
+            &[
+                system_program.to_account_info(),
+                funder.to_account_info(),
+                source.to_account_info(),
+            ],
+        )?;
+        emit!(FaucetFunded {
+            spec: spec.key(),
+            funder: funder.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    pub fn fund_token(ctx: Context<FundToken>, amount: u64) -> Result<()> {
+        let FundToken {
+            funder,
+            spec,
+            mint,
+            source,
+            funder_token_account,
+            token_program,
+        } = ctx.accounts;
+
+        msg!("Funding faucet with {} tokens", amount);
+        token_interface::transfer_checked(
+            CpiContext::new(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: funder_token_account.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: source.to_account_info(),
+                    authority: funder.to_account_info(),
+                },
+            ),
+            amount,
+            mint.decimals,
+        )?;
+        emit!(FaucetFunded {
+            spec: spec.key(),
+            funder: funder.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    pub fn close_receipt(ctx: Context<CloseReceipt>) -> Result<()> {
+        let CloseReceipt { payer, receipt } = ctx.accounts;
+        let receipt_data = Receipt::try_deserialize(&mut &receipt.try_borrow_data()?[..])?;
+        require_keys_eq!(receipt_data.payer, payer.key(), ErrorCode::ConstraintHasOne);
+        let unlock_slot = receipt_data.unlock_slot.ok_or(FaucetError::ReceiptLocked)?;
+        require!(
+            Clock::get()?.slot >= unlock_slot,
+            FaucetError::ReceiptLocked
+        );
+        msg!("Closing receipt for claim at slot {}", receipt_data.slot);
+
+        // The emptied account stays behind, since its address is what marks the work as redeemed.
+        // Only the rent for the claim data is refunded.
+        let refund = receipt
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(0));
+        **receipt.try_borrow_mut_lamports()? -= refund;
+        **payer.try_borrow_mut_lamports()? += refund;
+        receipt.realloc(0, false)?;
         Ok(())
     }
 
@@ -90,35 +381,115 @@ pub mod proof_of_work_faucet {
             system_program,
         } = ctx.accounts;
 
+        require!(receipt.owner != ctx.program_id, FaucetError::AlreadyClaimed);
+
         // Count the number of leading A's in the signer's public key.
-        let prefix_len = encode(signer.key().as_ref())
-            .into_string()
-            .chars()
-            .take_while(|ch| ch == &'A')
-            .count();
+        let prefix_len = prefix_len(&signer.key());
 
-        if prefix_len < spec.difficulty as usize {
+        let clock = Clock::get()?;
+        let difficulty = spec.effective_difficulty(clock.unix_timestamp);
+        if prefix_len < difficulty as usize {
             msg!(
                 "Public key does not meet difficulty requirement of {}: {}",
-                spec.difficulty,
+                difficulty,
                 signer.key()
             );
-            return Err(ProgramError::MissingRequiredSignature.into());
+            return err!(FaucetError::InsufficientDifficulty);
         }
+        spec.record_claim(clock.unix_timestamp);
+
+        let amount = spec.payout_amount(source.lamports(), Rent::get()?.minimum_balance(0))?;
+        spec.consume_budget(amount, clock.slot, clock.epoch)?;
+        spec.stats.record(amount, clock.slot, prefix_len as u8);
 
         msg!("Source wallet balance: {}", source.lamports());
-        msg!(
-            "Airdropping {} lamports to {}",
-            spec.amount.min(source.lamports()),
-            payer.key()
-        );
+        msg!("Airdropping {} lamports to {}", amount, payer.key());
 
         invoke_signed(
-            &system_instruction::transfer(
-                &source.key(),
-                &payer.key(),
-                spec.amount.min(source.lamports()),
-            ),
+            &system_instruction::transfer(&source.key(), &payer.key(), amount),
+            &[
+                system_program.to_account_info(),
+                payer.to_account_info(),
+                source.to_account_info(),
+            ],
+            &[&[b"source", spec.key().as_ref(), &[ctx.bumps["source"]]]],
+        )?;
+
+        // Create a receipt account after receiving the airdrop to lower the base SOL requirement.
+        create_account(
+            payer,
+            receipt,
+            system_program,
+            ctx.program_id,
+            &Rent::get()?,
+            spec.config.receipt_space(),
+            vec![
+                b"receipt".to_vec(),
+                signer.key().to_bytes().to_vec(),
+                spec.difficulty.to_le_bytes().to_vec(),
+                vec![ctx.bumps["receipt"]],
+            ],
+        )?;
+        write_receipt(
+            receipt,
+            &Receipt {
+                spec: spec.key(),
+                payer: payer.key(),
+                slot: clock.slot,
+                amount,
+                unlock_slot: spec.receipt_unlock_slot(clock.slot),
+            },
+        )?;
+        emit!(AirdropClaimed {
+            spec: spec.key(),
+            signer: signer.key(),
+            payer: payer.key(),
+            amount,
+            prefix_len: prefix_len as u8,
+        });
+        Ok(())
+    }
+
+    pub fn hash_airdrop(ctx: Context<HashAirdrop>, slot: u64, nonce: u64) -> Result<()> {
+        let HashAirdrop {
+            payer,
+            receipt,
+            spec,
+            source,
+            slot_hashes,
+            system_program,
+        } = ctx.accounts;
+
+        require!(receipt.owner != ctx.program_id, FaucetError::AlreadyClaimed);
+
+        let slot_hash = find_slot_hash(&slot_hashes.try_borrow_data()?, slot)
+            .ok_or(FaucetError::SlotHashNotFound)?;
+
+        // Count the number of leading zero bits in the hash of the recent slot, payer and nonce.
+        let zero_bits = leading_zero_bits(&challenge_hash(&slot_hash, &payer.key(), nonce));
+
+        let clock = Clock::get()?;
+        let difficulty = spec.effective_difficulty(clock.unix_timestamp);
+        if zero_bits < difficulty as u32 {
+            msg!(
+                "Hash for nonce {} at slot {} does not meet difficulty requirement of {}",
+                nonce,
+                slot,
+                difficulty,
+            );
+            return err!(FaucetError::InsufficientDifficulty);
+        }
+        spec.record_claim(clock.unix_timestamp);
+
+        let amount = spec.payout_amount(source.lamports(), Rent::get()?.minimum_balance(0))?;
+        spec.consume_budget(amount, clock.slot, clock.epoch)?;
+        spec.stats.record(amount, clock.slot, zero_bits as u8);
+
+        msg!("Source wallet balance: {}", source.lamports());
+        msg!("Airdropping {} lamports to {}", amount, payer.key());
+
+        invoke_signed(
+            &system_instruction::transfer(&source.key(), &payer.key(), amount),
             &[
                 system_program.to_account_info(),
                 payer.to_account_info(),
@@ -127,41 +498,732 @@ pub mod proof_of_work_faucet {
             &[&[b"source", spec.key().as_ref(), &[ctx.bumps["source"]]]],
         )?;
 
+        // The receipt is keyed by the solution so the same nonce cannot be redeemed twice.
+        create_account(
+            payer,
+            receipt,
+            system_program,
+            ctx.program_id,
+            &Rent::get()?,
+            spec.config.receipt_space(),
+            vec![
+                b"receipt".to_vec(),
+                payer.key().to_bytes().to_vec(),
+                slot.to_le_bytes().to_vec(),
+                nonce.to_le_bytes().to_vec(),
+                spec.difficulty.to_le_bytes().to_vec(),
+                vec![ctx.bumps["receipt"]],
+            ],
+        )?;
+        write_receipt(
+            receipt,
+            &Receipt {
+                spec: spec.key(),
+                payer: payer.key(),
+                slot: clock.slot,
+                amount,
+                // Keep the receipt until the challenge slot leaves SlotHashes so the nonce cannot be replayed
+                unlock_slot: spec.receipt_unlock_slot(clock.slot).map(|unlock_slot| {
+                    unlock_slot.max(slot.saturating_add(slot_hashes::MAX_ENTRIES as u64))
+                }),
+            },
+        )?;
+        emit!(AirdropClaimed {
+            spec: spec.key(),
+            signer: payer.key(),
+            payer: payer.key(),
+            amount,
+            prefix_len: zero_bits as u8,
+        });
+        Ok(())
+    }
+
+    pub fn airdrop_token(ctx: Context<AirdropToken>) -> Result<()> {
+        let AirdropToken {
+            payer,
+            signer,
+            receipt,
+            spec,
+            mint,
+            source,
+            destination,
+            token_program,
+            associated_token_program,
+            system_program,
+        } = ctx.accounts;
+
+        require!(receipt.owner != ctx.program_id, FaucetError::AlreadyClaimed);
+
+        // Count the number of leading A's in the signer's public key.
+        let prefix_len = prefix_len(&signer.key());
+
+        let clock = Clock::get()?;
+        let difficulty = spec.effective_difficulty(clock.unix_timestamp);
+        if prefix_len < difficulty as usize {
+            msg!(
+                "Public key does not meet difficulty requirement of {}: {}",
+                difficulty,
+                signer.key()
+            );
+            return err!(FaucetError::InsufficientDifficulty);
+        }
+        spec.record_claim(clock.unix_timestamp);
+
+        // Token accounts hold their rent separately from the balance
+        let amount = spec.payout_amount(source.amount, 0)?;
+        spec.consume_budget(amount, clock.slot, clock.epoch)?;
+        spec.stats.record(amount, clock.slot, prefix_len as u8);
+
+        msg!("Source token balance: {}", source.amount);
+        msg!("Airdropping {} tokens to {}", amount, payer.key());
+
+        // Create the payer's associated token account if it doesn't exist yet
+        associated_token::create_idempotent(CpiContext::new(
+            associated_token_program.to_account_info(),
+            CreateAssociatedToken {
+                payer: payer.to_account_info(),
+                associated_token: destination.to_account_info(),
+                authority: payer.to_account_info(),
+                mint: mint.to_account_info(),
+                system_program: system_program.to_account_info(),
+                token_program: token_program.to_account_info(),
+            },
+        ))?;
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: source.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: destination.to_account_info(),
+                    authority: spec.to_account_info(),
+                },
+                &[&[
+                    b"spec",
+                    spec.difficulty.to_le_bytes().as_ref(),
+                    spec.amount.to_le_bytes().as_ref(),
+                    spec.config.mode.seed(),
+                    mint.key().as_ref(),
+                    &[ctx.bumps["spec"]],
+                ]],
+            ),
+            amount,
+            mint.decimals,
+        )?;
+
         // Create a receipt account after receiving the airdrop to lower the base SOL requirement.
         create_account(
-            &payer,
-            &receipt,
+            payer,
+            receipt,
             system_program,
             ctx.program_id,
             &Rent::get()?,
-            0,
+            spec.config.receipt_space(),
             vec![
                 b"receipt".to_vec(),
                 signer.key().to_bytes().to_vec(),
                 spec.difficulty.to_le_bytes().to_vec(),
+                mint.key().to_bytes().to_vec(),
                 vec![ctx.bumps["receipt"]],
             ],
         )?;
+        write_receipt(
+            receipt,
+            &Receipt {
+                spec: spec.key(),
+                payer: payer.key(),
+                slot: clock.slot,
+                amount,
+                unlock_slot: spec.receipt_unlock_slot(clock.slot),
+            },
+        )?;
+        emit!(AirdropClaimed {
+            spec: spec.key(),
+            signer: signer.key(),
+            payer: payer.key(),
+            amount,
+            prefix_len: prefix_len as u8,
+        });
+        Ok(())
+    }
+
+    pub fn withdraw_token(ctx: Context<WithdrawToken>, amount: u64) -> Result<()> {
+        let WithdrawToken {
+            authority,
+            spec,
+            mint,
+            source,
+            destination,
+            token_program,
+            associated_token_program,
+            system_program,
+        } = ctx.accounts;
+
+        let amount = amount.min(source.amount);
+        msg!("Withdrawing {} tokens to {}", amount, authority.key());
+
+        associated_token::create_idempotent(CpiContext::new(
+            associated_token_program.to_account_info(),
+            CreateAssociatedToken {
+                payer: authority.to_account_info(),
+                associated_token: destination.to_account_info(),
+                authority: authority.to_account_info(),
+                mint: mint.to_account_info(),
+                system_program: system_program.to_account_info(),
+                token_program: token_program.to_account_info(),
+            },
+        ))?;
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: source.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: destination.to_account_info(),
+                    authority: spec.to_account_info(),
+                },
+                &[&[
+                    b"spec",
+                    spec.difficulty.to_le_bytes().as_ref(),
+                    spec.amount.to_le_bytes().as_ref(),
+                    spec.config.mode.seed(),
+                    mint.key().as_ref(),
+                    &[ctx.bumps["spec"]],
+                ]],
+            ),
+            amount,
+            mint.decimals,
+        )?;
+        emit!(FaucetWithdrawn {
+            spec: spec.key(),
+            authority: authority.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    pub fn close_token_faucet(ctx: Context<CloseTokenFaucet>) -> Result<()> {
+        let CloseTokenFaucet {
+            authority,
+            spec,
+            mint,
+            source,
+            destination,
+            token_program,
+            associated_token_program,
+            system_program,
+        } = ctx.accounts;
+
+        let remaining = source.amount;
+        msg!(
+            "Closing faucet and returning {} tokens to {}",
+            remaining,
+            authority.key()
+        );
+
+        let mint_key = mint.key();
+        let spec_seeds: &[&[u8]] = &[
+            b"spec",
+            &spec.difficulty.to_le_bytes(),
+            &spec.amount.to_le_bytes(),
+            spec.config.mode.seed(),
+            mint_key.as_ref(),
+            &[ctx.bumps["spec"]],
+        ];
+
+        // Drain the source token account so it can be closed
+        if remaining > 0 {
+            associated_token::create_idempotent(CpiContext::new(
+                associated_token_program.to_account_info(),
+                CreateAssociatedToken {
+                    payer: authority.to_account_info(),
+                    associated_token: destination.to_account_info(),
+                    authority: authority.to_account_info(),
+                    mint: mint.to_account_info(),
+                    system_program: system_program.to_account_info(),
+                    token_program: token_program.to_account_info(),
+                },
+            ))?;
+
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    TransferChecked {
+                        from: source.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: spec.to_account_info(),
+                    },
+                    &[spec_seeds],
+                ),
+                remaining,
+                mint.decimals,
+            )?;
+        }
+
+        token_interface::close_account(CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            CloseAccount {
+                account: source.to_account_info(),
+                destination: authority.to_account_info(),
+                authority: spec.to_account_info(),
+            },
+            &[spec_seeds],
+        ))?;
+        emit!(FaucetWithdrawn {
+            spec: spec.key(),
+            authority: authority.key(),
+            amount: remaining,
+        });
+        Ok(())
+    }
+}
+
+/// Clients map error codes back to variants with `FaucetError::try_from(code - ERROR_CODE_OFFSET)`.
+#[error_code]
+#[derive(num_enum::TryFromPrimitive)]
+pub enum FaucetError {
+    #[msg("Faucet spec does not use this proof of work mode")]
+    WrongMode,
+    #[msg("Slot hash not found. The slot is either too old or in the future")]
+    SlotHashNotFound,
+    #[msg("Proof of work does not meet the difficulty requirement")]
+    InsufficientDifficulty,
+    #[msg("Invalid faucet config")]
+    InvalidConfig,
+    #[msg("Faucet has reached its throughput cap for the current window")]
+    BudgetExceeded,
+    #[msg("Proof of work has already been redeemed at this difficulty")]
+    AlreadyClaimed,
+    #[msg("Faucet has no balance left to pay out")]
+    FaucetEmpty,
+    #[msg("Faucet balance is below the reward amount")]
+    InsufficientFaucetBalance,
+    #[msg("Receipt cannot be closed yet")]
+    ReceiptLocked,
+    #[msg("Faucet spec already uses the current layout")]
+    SpecUpToDate,
+}
+
+#[event]
+pub struct FaucetCreated {
+    pub spec: Pubkey,
+    pub authority: Pubkey,
+    pub difficulty: u8,
+    pub amount: u64,
+    pub mode: Mode,
+    pub mint: Option<Pubkey>,
+}
+
+#[event]
+pub struct AirdropClaimed {
+    pub spec: Pubkey,
+    /// The vanity keypair that signed the claim. Equal to `payer` in hash mode.
+    pub signer: Pubkey,
+    pub payer: Pubkey,
+    pub amount: u64,
+    /// Leading 'A's of `signer`, or leading zero bits of the challenge hash in hash mode.
+    pub prefix_len: u8,
+}
+
+#[event]
+pub struct FaucetFunded {
+    pub spec: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct FaucetWithdrawn {
+    pub spec: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
+
+/// How a claimant proves that they did the work for an airdrop.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// The `signer` pubkey must start with `difficulty` base58 'A's.
+    #[default]
+    Vanity,
+    /// sha256(recent slot hash || payer || nonce) must have `difficulty` leading zero bits.
+    Hash,
+}
+
+impl Mode {
+    /// Extra spec seed that keeps specs of different modes at different addresses.
+    /// Vanity specs use no extra seed so their addresses are unchanged.
+    pub fn seed(&self) -> &'static [u8] {
+        match self {
+            Mode::Vanity => b"",
+            Mode::Hash => b"hash",
+        }
+    }
+
+    /// How many times more work each additional unit of difficulty requires.
+    pub fn work_factor(&self) -> u128 {
+        match self {
+            Mode::Vanity => 58,
+            Mode::Hash => 2,
+        }
+    }
+}
+
+/// Retargets the difficulty of a faucet towards a target claim rate.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AdaptiveConfig {
+    pub target_claims_per_hour: u32,
+    /// Length of a retarget window in seconds.
+    pub window: i64,
+    pub min_difficulty: u8,
+    pub max_difficulty: u8,
+}
+
+impl AdaptiveConfig {
+    pub const LEN: usize = 4 + 8 + 1 + 1;
+}
+
+/// Window over which a faucet's throughput cap is counted.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapWindow {
+    Epoch,
+    Slots(u64),
+}
+
+impl CapWindow {
+    pub const LEN: usize = 1 + 8;
+
+    /// Index of the window containing the given slot and epoch.
+    pub fn index(&self, slot: u64, epoch: u64) -> u64 {
+        match *self {
+            CapWindow::Epoch => epoch,
+            CapWindow::Slots(slots) => slot / slots.max(1),
+        }
+    }
+}
+
+/// Limits how much a faucet pays out per window. `None` leaves that dimension uncapped.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThroughputCap {
+    pub window: CapWindow,
+    pub max_lamports: Option<u64>,
+    pub max_claims: Option<u32>,
+}
+
+impl ThroughputCap {
+    pub const LEN: usize = CapWindow::LEN + (1 + 8) + (1 + 4);
+}
+
+/// Lifetime payout statistics of a faucet.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaucetStats {
+    pub total_claims: u64,
+    /// Lamports, or token base units for token faucets.
+    pub total_distributed: u64,
+    pub last_claim_slot: u64,
+    /// Highest prefix length (or leading zero bits in hash mode) that has claimed.
+    pub max_prefix_len: u8,
+}
+
+impl FaucetStats {
+    pub const LEN: usize = 8 + 8 + 8 + 1;
+
+    pub fn record(&mut self, amount: u64, slot: u64, prefix_len: u8) {
+        self.total_claims = self.total_claims.saturating_add(1);
+        self.total_distributed = self.total_distributed.saturating_add(amount);
+        self.last_claim_slot = slot;
+        self.max_prefix_len = self.max_prefix_len.max(prefix_len);
+    }
+}
+
+/// Lamports and claims still available in the current cap window. `None` means uncapped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RemainingBudget {
+    pub lamports: Option<u64>,
+    pub claims: Option<u32>,
+}
+
+/// What a claim pays when the faucet holds less than the reward amount.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayoutPolicy {
+    /// Fail the claim so the miner keeps their proof of work for another faucet.
+    #[default]
+    FullAmount,
+    /// Pay out whatever is left, as long as it is not zero.
+    Partial,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaucetConfig {
+    pub mode: Mode,
+    /// Fixed difficulty if `None`.
+    pub adaptive: Option<AdaptiveConfig>,
+    /// Unlimited throughput if `None`.
+    pub cap: Option<ThroughputCap>,
+    pub payout: PayoutPolicy,
+    /// Slots after a claim before its receipt's claim data can be closed for rent. Receipts are
+    /// permanent if `None`. A closed receipt still blocks its key from claiming again.
+    pub receipt_cooldown: Option<u64>,
+}
+
+impl FaucetConfig {
+    pub const LEN: usize = 1 + (1 + AdaptiveConfig::LEN) + (1 + ThroughputCap::LEN) + 1 + (1 + 8);
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(adaptive) = self.adaptive {
+            require!(
+                adaptive.target_claims_per_hour > 0
+                    && adaptive.window > 0
+                    && adaptive.min_difficulty <= adaptive.max_difficulty,
+                FaucetError::InvalidConfig
+            );
+        }
+        if let Some(cap) = self.cap {
+            require!(
+                cap.window != CapWindow::Slots(0),
+                FaucetError::InvalidConfig
+            );
+        }
+        // A receipt whose claim data can be closed in the slot it was created in is not worth
+        // paying rent for
+        require!(self.receipt_cooldown != Some(0), FaucetError::InvalidConfig);
         Ok(())
     }
+
+    /// Space a receipt for a claim on this faucet takes up. Receipts that can never be closed only
+    /// need to exist, so they hold no data and cost the claimant the least rent.
+    pub fn receipt_space(&self) -> u64 {
+        match self.receipt_cooldown {
+            Some(_) => 8 + Receipt::LEN as u64,
+            None => 0,
+        }
+    }
 }
 
 #[account]
 pub struct Difficulty {
     pub difficulty: u8,
     pub amount: u64,
+    pub authority: Pubkey,
+    pub config: FaucetConfig,
+    /// Difficulty as of the last retarget. Always equal to `difficulty` for fixed faucets.
+    pub current_difficulty: u8,
+    /// Unix timestamp at which the current retarget window started.
+    pub window_start: i64,
+    pub window_claims: u32,
+    /// Index of the cap window that the budget counters below belong to.
+    pub budget_window: u64,
+    pub budget_lamports_used: u64,
+    pub budget_claims_used: u32,
+    /// Token faucets pay out this mint instead of SOL.
+    pub mint: Option<Pubkey>,
+    pub stats: FaucetStats,
+}
+
+impl Difficulty {
+    /// Size of specs created before the authority was added, which only held `difficulty` and
+    /// `amount`. They have to be migrated with `migrate_spec` before they deserialize.
+    pub const LEGACY_LEN: usize = 1 + 8;
+
+    pub const LEN: usize =
+        1 + 8 + 32 + FaucetConfig::LEN + 1 + 8 + 4 + 8 + 8 + 4 + (1 + 32) + FaucetStats::LEN;
+
+    pub fn init(
+        &mut self,
+        difficulty: u8,
+        amount: u64,
+        authority: Pubkey,
+        config: FaucetConfig,
+        mint: Option<Pubkey>,
+    ) -> Result<()> {
+        config.validate()?;
+        self.difficulty = difficulty;
+        self.amount = amount;
+        self.authority = authority;
+        self.config = config;
+        self.current_difficulty = match config.adaptive {
+            Some(adaptive) => difficulty.clamp(adaptive.min_difficulty, adaptive.max_difficulty),
+            None => difficulty,
+        };
+        self.window_start = Clock::get()?.unix_timestamp;
+        self.mint = mint;
+        Ok(())
+    }
+
+    /// First slot at which a receipt for a claim made at `claim_slot` can be closed.
+    pub fn receipt_unlock_slot(&self, claim_slot: u64) -> Option<u64> {
+        self.config
+            .receipt_cooldown
+            .map(|cooldown| claim_slot.saturating_add(cooldown))
+    }
+
+    /// Amount to pay out of a source holding `balance`, according to the payout policy. A payout
+    /// either empties the source or leaves at least `rent_floor` in it, since the runtime rejects
+    /// transfers that leave an account with less than its rent-exempt minimum.
+    pub fn payout_amount(&self, balance: u64, rent_floor: u64) -> Result<u64> {
+        let payable = if balance <= self.amount {
+            balance
+        } else {
+            self.amount.min(balance - rent_floor.min(balance))
+        };
+        match self.config.payout {
+            PayoutPolicy::FullAmount => {
+                require!(
+                    payable == self.amount,
+                    FaucetError::InsufficientFaucetBalance
+                );
+                Ok(self.amount)
+            }
+            PayoutPolicy::Partial => {
+                require!(payable > 0, FaucetError::FaucetEmpty);
+                Ok(payable)
+            }
+        }
+    }
+
+    /// Throughput left in the cap window containing the given slot and epoch, or `None` if the
+    /// faucet is uncapped.
+    pub fn remaining_budget(&self, slot: u64, epoch: u64) -> Option<RemainingBudget> {
+        let cap = self.config.cap?;
+        let (lamports_used, claims_used) = if cap.window.index(slot, epoch) == self.budget_window {
+            (self.budget_lamports_used, self.budget_claims_used)
+        } else {
+            (0, 0)
+        };
+        Some(RemainingBudget {
+            lamports: cap
+                .max_lamports
+                .map(|max| max.saturating_sub(lamports_used)),
+            claims: cap.max_claims.map(|max| max.saturating_sub(claims_used)),
+        })
+    }
+
+    /// Counts a claim of `amount` lamports against the current cap window.
+    pub fn consume_budget(&mut self, amount: u64, slot: u64, epoch: u64) -> Result<()> {
+        if let Some(cap) = self.config.cap {
+            let window = cap.window.index(slot, epoch);
+            if window != self.budget_window {
+                self.budget_window = window;
+                self.budget_lamports_used = 0;
+                self.budget_claims_used = 0;
+            }
+            let lamports_used = self.budget_lamports_used.saturating_add(amount);
+            let claims_used = self.budget_claims_used.saturating_add(1);
+            if matches!(cap.max_lamports, Some(max) if lamports_used > max)
+                || matches!(cap.max_claims, Some(max) if claims_used > max)
+            {
+                msg!(
+                    "Throughput cap reached: {} lamports and {} claims used in window {}",
+                    self.budget_lamports_used,
+                    self.budget_claims_used,
+                    window
+                );
+                return err!(FaucetError::BudgetExceeded);
+            }
+            self.budget_lamports_used = lamports_used;
+            self.budget_claims_used = claims_used;
+        }
+        Ok(())
+    }
+
+    /// Difficulty that a claim made at `now` must meet.
+    pub fn effective_difficulty(&self, now: i64) -> u8 {
+        match self.config.adaptive {
+            Some(adaptive) if now.saturating_sub(self.window_start) >= adaptive.window => {
+                self.retarget(&adaptive, now)
+            }
+            _ => self.current_difficulty,
+        }
+    }
+
+    /// Records a claim made at `now`, retargeting first if the current window has elapsed.
+    pub fn record_claim(&mut self, now: i64) {
+        if let Some(adaptive) = self.config.adaptive {
+            if now.saturating_sub(self.window_start) >= adaptive.window {
+                self.current_difficulty = self.retarget(&adaptive, now);
+                self.window_start = now;
+                self.window_claims = 0;
+            }
+            self.window_claims = self.window_claims.saturating_add(1);
+        }
+    }
+
+    /// Moves the difficulty by the number of steps that brings the observed claim rate closest to
+    /// the target, where each step multiplies the expected work by the mode's work factor.
+    fn retarget(&self, adaptive: &AdaptiveConfig, now: i64) -> u8 {
+        let elapsed = now.saturating_sub(self.window_start).max(1) as u128;
+        let windows = elapsed / adaptive.window.max(1) as u128;
+        // Both rates are scaled by `elapsed * 3600` to stay in integers.
+        let observed = self.window_claims as u128 * 3600;
+        let target = adaptive.target_claims_per_hour as u128 * elapsed;
+        let factor = self.config.mode.work_factor();
+
+        let next = if observed == 0 {
+            // Nobody managed a single claim, so ease off one step per elapsed window
+            self.current_difficulty
+                .saturating_sub(windows.min(u8::MAX as u128) as u8)
+        } else if observed > target {
+            self.current_difficulty
+                .saturating_add(log_steps(observed, target, factor))
+        } else {
+            self.current_difficulty
+                .saturating_sub(log_steps(target, observed, factor))
+        };
+        next.clamp(adaptive.min_difficulty, adaptive.max_difficulty)
+    }
+}
+
+/// Rounds log_factor(high / low) to the nearest step, i.e. returns the largest `k` such that
+/// `high / low >= factor^(k - 1/2)`.
+fn log_steps(high: u128, low: u128, factor: u128) -> u8 {
+    let high_squared = high.saturating_mul(high);
+    let low_squared = low.saturating_mul(low);
+    let mut steps = 0;
+    // factor^(2k - 1) for k = steps + 1
+    let mut threshold = factor;
+    while steps < u8::MAX && high_squared >= low_squared.saturating_mul(threshold) {
+        steps += 1;
+        threshold = threshold.saturating_mul(factor * factor);
+    }
+    steps
+}
+
+/// Proof that a claim was made. Its address is derived from the proof of work and the faucet's
+/// difficulty, so it also stops the same work from being redeemed twice at that difficulty.
+/// Receipts of faucets without a receipt cooldown are created empty, and closing a receipt empties
+/// it rather than deleting it, so the work stays redeemed.
+#[account]
+pub struct Receipt {
+    pub spec: Pubkey,
+    /// Paid the claim's rent and gets it back when the receipt is closed.
+    pub payer: Pubkey,
+    pub slot: u64,
+    pub amount: u64,
+    /// The receipt can be closed from this slot on. Never if `None`.
+    pub unlock_slot: Option<u64>,
+}
+
+impl Receipt {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + (1 + 8);
 }
 
 #[derive(Accounts)]
-#[instruction(difficulty: u8, amount: u64)]
+#[instruction(difficulty: u8, amount: u64, config: FaucetConfig)]
 pub struct Create<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
         init,
-        seeds=[b"spec", difficulty.to_le_bytes().as_ref(), amount.to_le_bytes().as_ref()],
+        seeds=[
+            b"spec",
+            difficulty.to_le_bytes().as_ref(),
+            amount.to_le_bytes().as_ref(),
+            config.mode.seed(),
+        ],
         bump,
-        space=8 + 1 + 8,
+        space=8 + Difficulty::LEN,
         payer=payer,
     )]
     pub spec: Account<'info, Difficulty>,
@@ -181,7 +1243,144 @@ pub struct Airdrop<'info> {
     )]
     pub receipt: UncheckedAccount<'info>,
     #[account(
-        seeds=[b"spec", spec.difficulty.to_le_bytes().as_ref(), spec.amount.to_le_bytes().as_ref()],
+        mut,
+        constraint = spec.config.mode == Mode::Vanity @ FaucetError::WrongMode,
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+        ],
+        bump,
+    )]
+    pub spec: Account<'info, Difficulty>,
+    /// CHECK: Trust me bro
+    #[account(mut, seeds=[b"source", spec.key().as_ref()], bump)]
+    pub source: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(slot: u64, nonce: u64)]
+pub struct HashAirdrop<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Trust me bro
+    #[account(
+        mut,
+        seeds=[
+            b"receipt",
+            payer.key().as_ref(),
+            slot.to_le_bytes().as_ref(),
+            nonce.to_le_bytes().as_ref(),
+            spec.difficulty.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub receipt: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        constraint = spec.config.mode == Mode::Hash @ FaucetError::WrongMode,
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+        ],
+        bump,
+    )]
+    pub spec: Account<'info, Difficulty>,
+    /// CHECK: Trust me bro
+    #[account(mut, seeds=[b"source", spec.key().as_ref()], bump)]
+    pub source: UncheckedAccount<'info>,
+    /// CHECK: Checked against the SlotHashes sysvar id
+    #[account(address = sysvar::slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(difficulty: u8, amount: u64)]
+pub struct MigrateSpec<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: A legacy vanity spec, which no longer deserializes as `Difficulty`. Its layout is
+    /// checked by the instruction
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds=[b"spec", difficulty.to_le_bytes().as_ref(), amount.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub spec: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        has_one = authority,
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+        ],
+        bump,
+    )]
+    pub spec: Account<'info, Difficulty>,
+    /// CHECK: Trust me bro
+    #[account(mut, seeds=[b"source", spec.key().as_ref()], bump)]
+    pub source: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Fund<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    #[account(
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+        ],
+        bump,
+    )]
+    pub spec: Account<'info, Difficulty>,
+    /// CHECK: Trust me bro
+    #[account(mut, seeds=[b"source", spec.key().as_ref()], bump)]
+    pub source: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseReceipt<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Deserialized as a `Receipt` by the handler, which empties the account instead of
+    /// closing it
+    #[account(mut, owner = crate::ID)]
+    pub receipt: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseFaucet<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        close = authority,
+        has_one = authority,
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+        ],
         bump,
     )]
     pub spec: Account<'info, Difficulty>,
@@ -190,3 +1389,407 @@ pub struct Airdrop<'info> {
     pub source: UncheckedAccount<'info>,
     pub system_program: Program<'info, System>,
 }
+
+#[derive(Accounts)]
+#[instruction(difficulty: u8, amount: u64, config: FaucetConfig)]
+pub struct CreateToken<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        seeds=[
+            b"spec",
+            difficulty.to_le_bytes().as_ref(),
+            amount.to_le_bytes().as_ref(),
+            config.mode.seed(),
+            mint.key().as_ref(),
+        ],
+        bump,
+        space=8 + Difficulty::LEN,
+        payer=payer,
+    )]
+    pub spec: Box<Account<'info, Difficulty>>,
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init,
+        seeds=[b"source", spec.key().as_ref()],
+        bump,
+        payer=payer,
+        token::mint=mint,
+        token::authority=spec,
+    )]
+    pub source: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+// SOL instructions derive the spec without the mint seed, so they reject token specs.
+#[derive(Accounts)]
+pub struct AirdropToken<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub signer: Signer<'info>,
+    /// CHECK: Trust me bro
+    #[account(
+        mut,
+        seeds=[
+            b"receipt",
+            signer.key().as_ref(),
+            spec.difficulty.to_le_bytes().as_ref(),
+            mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub receipt: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+            mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub spec: Box<Account<'info, Difficulty>>,
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, seeds=[b"source", spec.key().as_ref()], bump)]
+    pub source: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The payer's associated token account, checked by the associated token program
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundToken<'info> {
+    pub funder: Signer<'info>,
+    #[account(
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+            mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub spec: Box<Account<'info, Difficulty>>,
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, seeds=[b"source", spec.key().as_ref()], bump)]
+    pub source: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Token account the funder transfers from. Ownership is checked by the token program.
+    #[account(mut)]
+    pub funder_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawToken<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        has_one = authority,
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+            mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub spec: Box<Account<'info, Difficulty>>,
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, seeds=[b"source", spec.key().as_ref()], bump)]
+    pub source: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The authority's associated token account, checked by the associated token program
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTokenFaucet<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        close = authority,
+        has_one = authority,
+        seeds=[
+            b"spec",
+            spec.difficulty.to_le_bytes().as_ref(),
+            spec.amount.to_le_bytes().as_ref(),
+            spec.config.mode.seed(),
+            mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub spec: Box<Account<'info, Difficulty>>,
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, seeds=[b"source", spec.key().as_ref()], bump)]
+    pub source: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The authority's associated token account, checked by the associated token program
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(config: FaucetConfig) -> Difficulty {
+        Difficulty {
+            difficulty: 4,
+            amount: 100_000_000,
+            authority: Pubkey::default(),
+            config,
+            current_difficulty: 4,
+            window_start: 0,
+            window_claims: 0,
+            budget_window: 0,
+            budget_lamports_used: 0,
+            budget_claims_used: 0,
+            mint: None,
+            stats: FaucetStats::default(),
+        }
+    }
+
+    fn adaptive(mode: Mode) -> Difficulty {
+        spec(FaucetConfig {
+            mode,
+            adaptive: Some(AdaptiveConfig {
+                target_claims_per_hour: 60,
+                window: 3600,
+                min_difficulty: 2,
+                max_difficulty: 6,
+            }),
+            ..FaucetConfig::default()
+        })
+    }
+
+    /// Effective difficulty once the first window has elapsed, after `claims` claims in it.
+    fn retargeted(mode: Mode, claims: u32) -> u8 {
+        let mut spec = adaptive(mode);
+        spec.window_claims = claims;
+        spec.effective_difficulty(3600)
+    }
+
+    #[test]
+    fn retargets_towards_target_rate() {
+        // On target
+        assert_eq!(retargeted(Mode::Vanity, 60), 4);
+        assert_eq!(retargeted(Mode::Vanity, 100), 4);
+        // Claimed 58 and 58^2 times too often
+        assert_eq!(retargeted(Mode::Vanity, 60 * 58), 5);
+        assert_eq!(retargeted(Mode::Vanity, 60 * 58 * 58), 6);
+        // Claimed 60 times too rarely
+        assert_eq!(retargeted(Mode::Vanity, 1), 3);
+        // Each step only doubles the work in hash mode
+        assert_eq!(retargeted(Mode::Hash, 60 * 4), 6);
+        assert_eq!(retargeted(Mode::Hash, 15), 2);
+    }
+
+    #[test]
+    fn retarget_without_claims_steps_down_per_window() {
+        let spec = adaptive(Mode::Vanity);
+        // Nothing changes until the window has elapsed
+        assert_eq!(spec.effective_difficulty(3599), 4);
+        assert_eq!(spec.effective_difficulty(3600), 3);
+        assert_eq!(spec.effective_difficulty(2 * 3600), 2);
+    }
+
+    #[test]
+    fn retarget_is_clamped() {
+        assert_eq!(retargeted(Mode::Vanity, u32::MAX), 6);
+        assert_eq!(adaptive(Mode::Vanity).effective_difficulty(100 * 3600), 2);
+
+        let mut spec = adaptive(Mode::Vanity);
+        spec.window_claims = u32::MAX;
+        spec.record_claim(3600);
+        assert_eq!(spec.current_difficulty, 6);
+        // Recording the claim started a new window that it is the only claim in
+        assert_eq!((spec.window_start, spec.window_claims), (3600, 1));
+    }
+
+    fn capped(window: CapWindow, max_lamports: Option<u64>, max_claims: Option<u32>) -> Difficulty {
+        spec(FaucetConfig {
+            cap: Some(ThroughputCap {
+                window,
+                max_lamports,
+                max_claims,
+            }),
+            ..FaucetConfig::default()
+        })
+    }
+
+    #[test]
+    fn budget_resets_when_slot_window_rolls_over() {
+        let mut spec = capped(CapWindow::Slots(100), None, Some(2));
+        spec.consume_budget(10, 100, 0).unwrap();
+        spec.consume_budget(10, 199, 0).unwrap();
+        assert_eq!(
+            spec.consume_budget(10, 199, 0),
+            Err(FaucetError::BudgetExceeded.into())
+        );
+        assert_eq!(
+            spec.remaining_budget(199, 0),
+            Some(RemainingBudget {
+                lamports: None,
+                claims: Some(0),
+            })
+        );
+
+        // Slot 200 starts the next window
+        assert_eq!(spec.remaining_budget(200, 0).unwrap().claims, Some(2));
+        spec.consume_budget(10, 200, 0).unwrap();
+        assert_eq!((spec.budget_window, spec.budget_claims_used), (2, 1));
+    }
+
+    #[test]
+    fn budget_resets_when_epoch_rolls_over() {
+        let mut spec = capped(CapWindow::Epoch, Some(30), None);
+        spec.consume_budget(30, 1_000, 5).unwrap();
+        // The slot does not matter within an epoch
+        assert_eq!(
+            spec.consume_budget(1, 500_000, 5),
+            Err(FaucetError::BudgetExceeded.into())
+        );
+        assert_eq!(spec.remaining_budget(500_000, 5).unwrap().lamports, Some(0));
+
+        assert_eq!(spec.remaining_budget(0, 6).unwrap().lamports, Some(30));
+        spec.consume_budget(20, 0, 6).unwrap();
+        assert_eq!(spec.remaining_budget(0, 6).unwrap().lamports, Some(10));
+    }
+
+    #[test]
+    fn budget_is_exceeded_only_past_the_cap() {
+        let mut faucet = capped(CapWindow::Epoch, Some(100), Some(3));
+        // Landing exactly on the lamport cap is allowed
+        faucet.consume_budget(60, 0, 0).unwrap();
+        faucet.consume_budget(40, 0, 0).unwrap();
+        assert_eq!(
+            faucet.consume_budget(1, 0, 0),
+            Err(FaucetError::BudgetExceeded.into())
+        );
+        // A rejected claim uses up nothing
+        assert_eq!(
+            (faucet.budget_lamports_used, faucet.budget_claims_used),
+            (100, 2)
+        );
+
+        let mut faucet = capped(CapWindow::Epoch, Some(100), Some(3));
+        for _ in 0..3 {
+            faucet.consume_budget(0, 0, 0).unwrap();
+        }
+        assert_eq!(
+            faucet.consume_budget(0, 0, 0),
+            Err(FaucetError::BudgetExceeded.into())
+        );
+
+        let mut uncapped = spec(FaucetConfig::default());
+        uncapped.consume_budget(u64::MAX, 0, 0).unwrap();
+        assert_eq!(uncapped.remaining_budget(0, 0), None);
+    }
+
+    fn paying(payout: PayoutPolicy) -> Difficulty {
+        spec(FaucetConfig {
+            payout,
+            ..FaucetConfig::default()
+        })
+    }
+
+    #[test]
+    fn full_amount_payouts_keep_the_source_rent_exempt() {
+        let faucet = paying(PayoutPolicy::FullAmount);
+        let amount = faucet.amount;
+        let rent = 890_880;
+        assert_eq!(
+            faucet.payout_amount(amount - 1, rent),
+            Err(FaucetError::InsufficientFaucetBalance.into())
+        );
+        // Emptying the source is fine
+        assert_eq!(faucet.payout_amount(amount, rent), Ok(amount));
+        // Leaving dust below the rent-exempt minimum is not
+        assert_eq!(
+            faucet.payout_amount(amount + 1, rent),
+            Err(FaucetError::InsufficientFaucetBalance.into())
+        );
+        assert_eq!(
+            faucet.payout_amount(amount + rent - 1, rent),
+            Err(FaucetError::InsufficientFaucetBalance.into())
+        );
+        assert_eq!(faucet.payout_amount(amount + rent, rent), Ok(amount));
+        // Token sources have no floor
+        assert_eq!(faucet.payout_amount(amount + 1, 0), Ok(amount));
+    }
+
+    #[test]
+    fn partial_payouts_keep_the_source_rent_exempt() {
+        let faucet = paying(PayoutPolicy::Partial);
+        let amount = faucet.amount;
+        let rent = 890_880;
+        assert_eq!(
+            faucet.payout_amount(0, rent),
+            Err(FaucetError::FaucetEmpty.into())
+        );
+        assert_eq!(faucet.payout_amount(1, rent), Ok(1));
+        assert_eq!(faucet.payout_amount(amount - 1, rent), Ok(amount - 1));
+        assert_eq!(faucet.payout_amount(amount, rent), Ok(amount));
+        // Pays what is left above the rent-exempt minimum
+        assert_eq!(
+            faucet.payout_amount(amount + 1, rent),
+            Ok(amount + 1 - rent)
+        );
+        assert_eq!(faucet.payout_amount(amount + rent, rent), Ok(amount));
+        assert_eq!(faucet.payout_amount(u64::MAX, rent), Ok(amount));
+    }
+
+    #[test]
+    fn validates_config() {
+        assert_eq!(FaucetConfig::default().validate(), Ok(()));
+        let cooldown = |receipt_cooldown| FaucetConfig {
+            receipt_cooldown,
+            ..FaucetConfig::default()
+        };
+        assert_eq!(cooldown(Some(1)).validate(), Ok(()));
+        assert_eq!(
+            cooldown(Some(0)).validate(),
+            Err(FaucetError::InvalidConfig.into())
+        );
+        assert_eq!(cooldown(None).receipt_space(), 0);
+        assert_eq!(cooldown(Some(1)).receipt_space(), 8 + Receipt::LEN as u64);
+
+        let capped = FaucetConfig {
+            cap: Some(ThroughputCap {
+                window: CapWindow::Slots(0),
+                max_lamports: None,
+                max_claims: Some(1),
+            }),
+            ..FaucetConfig::default()
+        };
+        assert_eq!(capped.validate(), Err(FaucetError::InvalidConfig.into()));
+        let mut adaptive = adaptive(Mode::Vanity).config;
+        assert_eq!(adaptive.validate(), Ok(()));
+        adaptive.adaptive.as_mut().unwrap().min_difficulty = 7;
+        assert_eq!(adaptive.validate(), Err(FaucetError::InvalidConfig.into()));
+    }
+
+    #[test]
+    fn fixed_difficulty_never_retargets() {
+        let mut spec = spec(FaucetConfig::default());
+        spec.window_claims = 1_000;
+        assert_eq!(spec.effective_difficulty(i64::MAX), 4);
+        spec.record_claim(i64::MAX);
+        assert_eq!((spec.current_difficulty, spec.window_claims), (4, 1_000));
+    }
+}
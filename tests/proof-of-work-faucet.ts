@@ -3,9 +3,22 @@ import { Program } from "@coral-xyz/anchor";
 import { ProofOfWorkFaucet } from "../target/types/proof_of_work_faucet";
 import {
   Keypair,
+  SYSVAR_SLOT_HASHES_PUBKEY,
   Transaction,
   sendAndConfirmTransaction,
 } from "@solana/web3.js";
+import {
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createMint,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from "@solana/spl-token";
+import { createHash } from "crypto";
+import { assert } from "chai";
 
 describe("proof-of-work-faucet", () => {
   // Configure the client to use the local cluster.
@@ -14,6 +27,17 @@ describe("proof-of-work-faucet", () => {
   const program = anchor.workspace
     .ProofOfWorkFaucet as Program<ProofOfWorkFaucet>;
 
+  // Fails unless `call` is rejected by the program with the error `code`
+  const expectError = async (call: Promise<unknown>, code: string) => {
+    try {
+      await call;
+    } catch (e) {
+      assert.equal(e.error?.errorCode?.code, code, e.toString());
+      return;
+    }
+    assert.fail(`Expected ${code}`);
+  };
+
   it("Proof of proof of work working", async () => {
     const amount = new anchor.BN(10_000_000_000);
     const difficulty = 3;
@@ -35,7 +59,7 @@ describe("proof-of-work-faucet", () => {
 
     const newUser = Keypair.generate();
     const tx = await program.methods
-      .create(difficulty, amount)
+      .create(difficulty, amount, { mode: { vanity: {} }, adaptive: null, cap: null, payout: { fullAmount: {} }, receiptCooldown: null })
       .accounts({
         payer: program.provider.publicKey,
         spec,
@@ -93,6 +117,8 @@ describe("proof-of-work-faucet", () => {
       await program.provider.connection.getBalance(newUser.publicKey)
     );
 
+    // The grinded pubkey must sign, which the client checks before sending
+    let signatureError = null;
     try {
       await program.methods
         .airdrop()
@@ -107,8 +133,9 @@ describe("proof-of-work-faucet", () => {
         .signers([newUser])
         .rpc();
     } catch (e) {
-      console.log("The grinded pubkey must sign", e);
+      signatureError = e;
     }
+    assert.match(String(signatureError), /signature/i);
 
     const airdropTx = await program.methods
       .airdrop()
@@ -136,9 +163,20 @@ describe("proof-of-work-faucet", () => {
       "New user balance:",
       await program.provider.connection.getBalance(newUser.publicKey)
     );
+    console.log(
+      "Faucet stats:",
+      (await program.account.difficulty.fetch(spec)).stats
+    );
+    // Without a cooldown the receipt can never be closed, so it holds no data
+    const receiptAccount = await program.provider.connection.getAccountInfo(
+      receipt
+    );
+    assert.ok(receiptAccount.owner.equals(program.programId));
+    assert.equal(receiptAccount.data.length, 0);
 
-    try {
-      await program.methods
+    // The same signer cannot claim twice
+    await expectError(
+      program.methods
         .airdrop()
         .accounts({
           payer: newUser.publicKey,
@@ -149,34 +187,694 @@ describe("proof-of-work-faucet", () => {
           systemProgram: anchor.web3.SystemProgram.programId,
         })
         .signers([newUser, signerKey])
+        .rpc(),
+      "AlreadyClaimed"
+    );
+
+    const [invalidReceipt] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("receipt"),
+        invalidSignerKey.publicKey.toBuffer(),
+        Buffer.from([difficulty]),
+      ],
+      program.programId
+    );
+    await expectError(
+      program.methods
+        .airdrop()
+        .accounts({
+          payer: newUser.publicKey,
+          signer: invalidSignerKey.publicKey,
+          receipt: invalidReceipt,
+          spec,
+          source,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([newUser, invalidSignerKey])
+        .rpc(),
+      "InsufficientDifficulty"
+    );
+  });
+
+  it("Authority can withdraw from and close a faucet", async () => {
+    const amount = new anchor.BN(1_000_000);
+    const difficulty = 4;
+
+    const [spec] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("spec"),
+        Buffer.from([difficulty]),
+        amount.toBuffer("le", 8),
+      ],
+      program.programId
+    );
+
+    const [source] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("source"), spec.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .create(difficulty, amount, { mode: { vanity: {} }, adaptive: null, cap: null, payout: { fullAmount: {} }, receiptCooldown: null })
+      .accounts({
+        payer: program.provider.publicKey,
+        spec,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .postInstructions([
+        anchor.web3.SystemProgram.transfer({
+          fromPubkey: program.provider.publicKey,
+          toPubkey: source,
+          lamports: 2_000_000_000,
+        }),
+      ])
+      .rpc();
+
+    const specAccount = await program.account.difficulty.fetch(spec);
+    console.log("Faucet authority:", specAccount.authority.toString());
+
+    const fundTx = await program.methods
+      .fund(new anchor.BN(500_000_000))
+      .accounts({
+        funder: program.provider.publicKey,
+        spec,
+        source,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+    const fundEvents = (
+      await program.provider.connection.getTransaction(fundTx, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      })
+    ).meta.logMessages
+      .filter((log) => log.startsWith("Program data: "))
+      .map((log) => program.coder.events.decode(log.slice("Program data: ".length)));
+    console.log("Fund events:", fundEvents);
+
+    const impostor = Keypair.generate();
+    // Only the authority can withdraw
+    await expectError(
+      program.methods
+        .withdraw(new anchor.BN(1_000_000_000))
+        .accounts({
+          authority: impostor.publicKey,
+          spec,
+          source,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([impostor])
+        .rpc(),
+      "ConstraintHasOne"
+    );
+
+    await program.methods
+      .withdraw(new anchor.BN(1_000_000_000))
+      .accounts({
+        authority: program.provider.publicKey,
+        spec,
+        source,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+    console.log(
+      "Source balance after withdraw:",
+      await program.provider.connection.getBalance(source)
+    );
+
+    await program.methods
+      .closeFaucet()
+      .accounts({
+        authority: program.provider.publicKey,
+        spec,
+        source,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+    console.log(
+      "Source balance after close:",
+      await program.provider.connection.getBalance(source)
+    );
+    console.log(
+      "Spec account after close:",
+      await program.provider.connection.getAccountInfo(spec)
+    );
+  });
+
+  it("Hash mode proof of work", async () => {
+    const amount = new anchor.BN(100_000_000);
+    const difficulty = 8;
+
+    const [spec] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("spec"),
+        Buffer.from([difficulty]),
+        amount.toBuffer("le", 8),
+        Buffer.from("hash"),
+      ],
+      program.programId
+    );
+
+    const [source] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("source"), spec.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .create(difficulty, amount, { mode: { hash: {} }, adaptive: null, cap: null, payout: { fullAmount: {} }, receiptCooldown: null })
+      .accounts({
+        payer: program.provider.publicKey,
+        spec,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .postInstructions([
+        anchor.web3.SystemProgram.transfer({
+          fromPubkey: program.provider.publicKey,
+          toPubkey: source,
+          lamports: 1_000_000_000,
+        }),
+      ])
+      .rpc();
+
+    // The most recent entry in the SlotHashes sysvar is the challenge
+    const slotHashes = await program.provider.connection.getAccountInfo(
+      SYSVAR_SLOT_HASHES_PUBKEY
+    );
+    const slot = new anchor.BN(slotHashes.data.subarray(8, 16), "le");
+    const slotHash = slotHashes.data.subarray(16, 48);
+
+    const leadingZeroBits = (hash: Buffer) => {
+      let bits = 0;
+      for (const byte of hash) {
+        if (byte === 0) {
+          bits += 8;
+          continue;
+        }
+        bits += Math.clz32(byte) - 24;
+        break;
+      }
+      return bits;
+    };
+
+    let nonce = new anchor.BN(0);
+    while (
+      leadingZeroBits(
+        createHash("sha256")
+          .update(slotHash)
+          .update(program.provider.publicKey.toBuffer())
+          .update(nonce.toBuffer("le", 8))
+          .digest()
+      ) < difficulty
+    ) {
+      nonce = nonce.addn(1);
+    }
+    console.log("Valid nonce", nonce.toString(), "for slot", slot.toString());
+
+    const [receipt] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("receipt"),
+        program.provider.publicKey.toBuffer(),
+        slot.toBuffer("le", 8),
+        nonce.toBuffer("le", 8),
+        Buffer.from([difficulty]),
+      ],
+      program.programId
+    );
+
+    const airdropTx = await program.methods
+      .hashAirdrop(slot, nonce)
+      .accounts({
+        payer: program.provider.publicKey,
+        receipt,
+        spec,
+        source,
+        slotHashes: SYSVAR_SLOT_HASHES_PUBKEY,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+    console.log("Airdropped with hash proof of work", airdropTx);
+
+    // The same nonce cannot be redeemed twice
+    await expectError(
+      program.methods
+        .hashAirdrop(slot, nonce)
+        .accounts({
+          payer: program.provider.publicKey,
+          receipt,
+          spec,
+          source,
+          slotHashes: SYSVAR_SLOT_HASHES_PUBKEY,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc(),
+      "AlreadyClaimed"
+    );
+  });
+
+  it("Closing a receipt after its cooldown refunds its rent but keeps the key claimed", async () => {
+    const difficulty = 1;
+
+    const createFaucet = async (
+      amount: anchor.BN,
+      receiptCooldown: anchor.BN
+    ) => {
+      const [spec] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("spec"),
+          Buffer.from([difficulty]),
+          amount.toBuffer("le", 8),
+        ],
+        program.programId
+      );
+      const [source] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("source"), spec.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .create(difficulty, amount, { mode: { vanity: {} }, adaptive: null, cap: null, payout: { fullAmount: {} }, receiptCooldown })
+        .accounts({
+          payer: program.provider.publicKey,
+          spec,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .postInstructions([
+          anchor.web3.SystemProgram.transfer({
+            fromPubkey: program.provider.publicKey,
+            toPubkey: source,
+            lamports: 1_000_000_000,
+          }),
+        ])
         .rpc();
-    } catch (e) {
-      console.log("Failed to use the same signer twice", e);
+      return [spec, source];
+    };
+
+    // A receipt whose data can be closed in the slot it was created in is not worth its rent
+    await expectError(
+      createFaucet(new anchor.BN(10_000_000), new anchor.BN(0)),
+      "InvalidConfig"
+    );
+
+    const amount = new anchor.BN(20_000_000);
+    const [spec, source] = await createFaucet(amount, new anchor.BN(1));
+    const [lockedSpec, lockedSource] = await createFaucet(
+      new anchor.BN(30_000_000),
+      new anchor.BN(1_000_000)
+    );
+
+    const groundKey = () => {
+      let key = Keypair.generate();
+      while (
+        key.publicKey.toString().slice(0, difficulty) !== "A".repeat(difficulty)
+      ) {
+        key = Keypair.generate();
+      }
+      return key;
+    };
+    const receiptFor = (signerKey: Keypair) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("receipt"),
+          signerKey.publicKey.toBuffer(),
+          Buffer.from([difficulty]),
+        ],
+        program.programId
+      )[0];
+    const claim = (
+      signerKey: Keypair,
+      spec: anchor.web3.PublicKey,
+      source: anchor.web3.PublicKey
+    ) =>
+      program.methods
+        .airdrop()
+        .accounts({
+          payer: program.provider.publicKey,
+          signer: signerKey.publicKey,
+          receipt: receiptFor(signerKey),
+          spec,
+          source,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([signerKey])
+        .rpc();
+    const closeReceipt = (signerKey: Keypair) =>
+      program.methods
+        .closeReceipt()
+        .accounts({
+          payer: program.provider.publicKey,
+          receipt: receiptFor(signerKey),
+        })
+        .rpc();
+
+    const signerKey = groundKey();
+    const balanceBefore = await program.provider.connection.getBalance(source);
+    await claim(signerKey, spec, source);
+    const receipt = await program.account.receipt.fetch(receiptFor(signerKey));
+    assert.ok(receipt.spec.equals(spec));
+    assert.ok(receipt.unlockSlot.eq(receipt.slot.addn(1)));
+
+    // Receipts are per difficulty, so the key is used up at every faucet of this difficulty
+    await expectError(
+      claim(signerKey, lockedSpec, lockedSource),
+      "AlreadyClaimed"
+    );
+
+    const lockedKey = groundKey();
+    await claim(lockedKey, lockedSpec, lockedSource);
+    await expectError(closeReceipt(lockedKey), "ReceiptLocked");
+
+    while (
+      (await program.provider.connection.getSlot()) <
+      receipt.unlockSlot.toNumber()
+    ) {
+      await new Promise((resolve) => setTimeout(resolve, 400));
     }
+    await closeReceipt(signerKey);
 
-    try {
-      const [invalidReceipt] = anchor.web3.PublicKey.findProgramAddressSync(
+    // Closing refunds all but the rent of an empty account, which stays behind
+    const closed = await program.provider.connection.getAccountInfo(
+      receiptFor(signerKey)
+    );
+    assert.ok(closed.owner.equals(program.programId));
+    assert.equal(closed.data.length, 0);
+    assert.equal(
+      closed.lamports,
+      await program.provider.connection.getMinimumBalanceForRentExemption(0)
+    );
+    await expectError(closeReceipt(signerKey), "AccountDiscriminatorNotFound");
+
+    // So the key still can't claim again
+    await expectError(claim(signerKey, spec, source), "AlreadyClaimed");
+    assert.equal(
+      await program.provider.connection.getBalance(source),
+      balanceBefore - amount.toNumber()
+    );
+  });
+
+  for (const [name, tokenProgram] of [
+    ["spl-token", TOKEN_PROGRAM_ID],
+    ["Token-2022", TOKEN_2022_PROGRAM_ID],
+  ] as const) {
+    it(`Token faucet with ${name}`, async () => {
+      const connection = program.provider.connection;
+      const authority = (
+        (program.provider as anchor.AnchorProvider).wallet as anchor.Wallet
+      ).payer;
+      const amount = new anchor.BN(25_000_000);
+      const difficulty = 1;
+
+      const mint = await createMint(
+        connection,
+        authority,
+        authority.publicKey,
+        null,
+        6,
+        Keypair.generate(),
+        undefined,
+        tokenProgram
+      );
+      const authorityTokens = await getOrCreateAssociatedTokenAccount(
+        connection,
+        authority,
+        mint,
+        authority.publicKey,
+        false,
+        undefined,
+        undefined,
+        tokenProgram
+      );
+      await mintTo(
+        connection,
+        authority,
+        mint,
+        authorityTokens.address,
+        authority,
+        1_000_000_000,
+        [],
+        undefined,
+        tokenProgram
+      );
+      const tokenBalance = async (address: anchor.web3.PublicKey) =>
+        Number(
+          (await getAccount(connection, address, undefined, tokenProgram))
+            .amount
+        );
+
+      const [spec] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("spec"),
+          Buffer.from([difficulty]),
+          amount.toBuffer("le", 8),
+          mint.toBuffer(),
+        ],
+        program.programId
+      );
+      const [source] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("source"), spec.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createToken(difficulty, amount, { mode: { vanity: {} }, adaptive: null, cap: null, payout: { fullAmount: {} }, receiptCooldown: null })
+        .accounts({
+          payer: authority.publicKey,
+          spec,
+          mint,
+          source,
+          tokenProgram,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+      const specAccount = await program.account.difficulty.fetch(spec);
+      assert.ok(specAccount.mint.equals(mint));
+
+      await program.methods
+        .fundToken(new anchor.BN(500_000_000))
+        .accounts({
+          funder: authority.publicKey,
+          spec,
+          mint,
+          source,
+          funderTokenAccount: authorityTokens.address,
+          tokenProgram,
+        })
+        .rpc();
+      assert.equal(await tokenBalance(source), 500_000_000);
+
+      // The claim creates the payer's associated token account
+      const claimant = Keypair.generate();
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          anchor.web3.SystemProgram.transfer({
+            fromPubkey: authority.publicKey,
+            toPubkey: claimant.publicKey,
+            lamports: 100_000_000,
+          })
+        ),
+        [authority]
+      );
+      let signerKey = Keypair.generate();
+      while (
+        signerKey.publicKey.toString().slice(0, difficulty) !==
+        "A".repeat(difficulty)
+      ) {
+        signerKey = Keypair.generate();
+      }
+      const [receipt] = anchor.web3.PublicKey.findProgramAddressSync(
         [
           Buffer.from("receipt"),
-          invalidSignerKey.publicKey.toBuffer(),
+          signerKey.publicKey.toBuffer(),
           Buffer.from([difficulty]),
+          mint.toBuffer(),
         ],
         program.programId
       );
+      const claimantTokens = getAssociatedTokenAddressSync(
+        mint,
+        claimant.publicKey,
+        false,
+        tokenProgram
+      );
+      const claim = () =>
+        program.methods
+          .airdropToken()
+          .accounts({
+            payer: claimant.publicKey,
+            signer: signerKey.publicKey,
+            receipt,
+            spec,
+            mint,
+            source,
+            destination: claimantTokens,
+            tokenProgram,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([claimant, signerKey])
+          .rpc();
+      await claim();
+      assert.equal(await tokenBalance(claimantTokens), amount.toNumber());
+      assert.equal(
+        await tokenBalance(source),
+        500_000_000 - amount.toNumber()
+      );
+      await expectError(claim(), "AlreadyClaimed");
+
+      // Only the authority can withdraw
+      const impostor = Keypair.generate();
+      await expectError(
+        program.methods
+          .withdrawToken(new anchor.BN(1))
+          .accounts({
+            authority: impostor.publicKey,
+            spec,
+            mint,
+            source,
+            destination: getAssociatedTokenAddressSync(
+              mint,
+              impostor.publicKey,
+              false,
+              tokenProgram
+            ),
+            tokenProgram,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([impostor])
+          .rpc(),
+        "ConstraintHasOne"
+      );
+
       await program.methods
-        .airdrop()
+        .withdrawToken(new anchor.BN(100_000_000))
         .accounts({
-          payer: newUser.publicKey,
-          signer: invalidSignerKey.publicKey,
-          receipt: invalidReceipt,
+          authority: authority.publicKey,
           spec,
+          mint,
           source,
+          destination: authorityTokens.address,
+          tokenProgram,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+      assert.equal(
+        await tokenBalance(source),
+        400_000_000 - amount.toNumber()
+      );
+      assert.equal(await tokenBalance(authorityTokens.address), 600_000_000);
+
+      // Closing returns the rest of the tokens and the source account's rent
+      await program.methods
+        .closeTokenFaucet()
+        .accounts({
+          authority: authority.publicKey,
+          spec,
+          mint,
+          source,
+          destination: authorityTokens.address,
+          tokenProgram,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
           systemProgram: anchor.web3.SystemProgram.programId,
         })
-        .signers([newUser, invalidSignerKey])
         .rpc();
+      assert.equal(
+        await tokenBalance(authorityTokens.address),
+        1_000_000_000 - amount.toNumber()
+      );
+      assert.isNull(await connection.getAccountInfo(source));
+      assert.isNull(await connection.getAccountInfo(spec));
+    });
+  }
+
+  it("Migrates a spec created before specs had an authority", async () => {
+    // Loaded into the validator from tests/fixtures in the pre-authority layout
+    const amount = new anchor.BN(50_000_000);
+    const difficulty = 2;
+
+    const [spec] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("spec"),
+        Buffer.from([difficulty]),
+        amount.toBuffer("le", 8),
+      ],
+      program.programId
+    );
+    const [source] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("source"), spec.toBuffer()],
+      program.programId
+    );
+
+    const legacy = await program.provider.connection.getAccountInfo(spec);
+    assert.equal(legacy.data.length, 8 + 1 + 8);
+    let decodeError = null;
+    try {
+      await program.account.difficulty.fetch(spec);
     } catch (e) {
-      console.log("Failed to use a signer with an insufficient difficulty", e);
+      decodeError = e;
     }
+    assert.isNotNull(decodeError, "A legacy spec should not deserialize");
+
+    await program.methods
+      .migrateSpec(difficulty, amount)
+      .accounts({
+        payer: program.provider.publicKey,
+        spec,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    const migrated = await program.account.difficulty.fetch(spec);
+    assert.equal(migrated.difficulty, difficulty);
+    assert.ok(migrated.amount.eq(amount));
+    assert.ok(migrated.authority.equals(anchor.web3.PublicKey.default));
+    assert.equal(migrated.currentDifficulty, difficulty);
+
+    // A spec can only be migrated once
+    await expectError(
+      program.methods
+        .migrateSpec(difficulty, amount)
+        .accounts({
+          payer: program.provider.publicKey,
+          spec,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc(),
+      "SpecUpToDate"
+    );
+
+    // The migrated faucet pays out again
+    let signerKey = Keypair.generate();
+    while (
+      signerKey.publicKey.toString().slice(0, difficulty) !==
+      "A".repeat(difficulty)
+    ) {
+      signerKey = Keypair.generate();
+    }
+    const [receipt] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("receipt"),
+        signerKey.publicKey.toBuffer(),
+        Buffer.from([difficulty]),
+      ],
+      program.programId
+    );
+    const balanceBefore = await program.provider.connection.getBalance(source);
+    await program.methods
+      .airdrop()
+      .accounts({
+        payer: program.provider.publicKey,
+        signer: signerKey.publicKey,
+        receipt,
+        spec,
+        source,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([signerKey])
+      .rpc();
+    assert.equal(
+      await program.provider.connection.getBalance(source),
+      balanceBefore - amount.toNumber()
+    );
   });
 });